@@ -1,10 +1,12 @@
 use std::{
-    collections::HashSet,
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
     error::{self, Error},
     path::PathBuf,
+    time::SystemTime,
 };
 
-use chrono::{NaiveDate, NaiveDateTime};
+use chrono::{Months, NaiveDate, NaiveDateTime, Timelike, Weekday};
 use crossterm::event::Event as CrosstermEvent;
 use indicium::simple::{SearchIndex, SearchIndexBuilder, SearchType};
 use ratatui::widgets::ScrollbarState;
@@ -12,8 +14,13 @@ use tui_input::{backend::crossterm::EventHandler, Input};
 
 use crate::{
     config::{self, Config},
-    entries::{self, EntryGroup, EntryRaw, EntryTitle},
+    entries::{self, Entry, EntryGroup, EntryRaw, EntryTitle, LogIndex},
+    error::AppError,
     files,
+    lock::LogLock,
+    parse,
+    search::{self, TitleUse},
+    ui::Areas,
 };
 
 /// Application result type.
@@ -23,6 +30,39 @@ pub type AppResult<T> = std::result::Result<T, Box<dyn error::Error>>;
 pub enum InputMode {
     Editing,
     Logging,
+    /// Choosing a day from the date picker
+    PickingDate,
+    /// Typing a date to go to
+    GoingToDate,
+    /// Browsing every history match in a popup
+    SearchingHistory,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionKind {
+    Project,
+    Tag,
+}
+
+/// Tab completion of the word before the cursor
+#[derive(Debug, Clone)]
+pub struct Completion {
+    pub kind: CompletionKind,
+    /// Known projects or tags starting with the word, most recently used first
+    pub candidates: Vec<String>,
+    /// Index into candidates of the completion in the input
+    pub index: usize,
+    /// Byte range of the input the completion replaces
+    start: usize,
+    end: usize,
+}
+
+/// What the log pane shows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum View {
+    Day,
+    /// Monday to Sunday of the week with the current date
+    Week,
 }
 
 pub struct App {
@@ -33,44 +73,116 @@ pub struct App {
     pub log_opening: String,
     pub log_input: String,
     pub log_closing: String,
+    /// The history suggestion in log_closing doesn't contain the input as one piece, so it
+    /// replaces the input instead of going around it
+    pub suggestion_replaces_input: bool,
     pub input_mode: InputMode,
+    pub view: View,
     /// Effective date
     pub current_date: NaiveDate,
     pub current_entries: EntryGroup,
-    pub entry_titles: Vec<EntryTitle>,
-    pub search_index: SearchIndex<usize>,
+    /// Every entry in the log by effective date
+    pub log_index: LogIndex,
+    /// History search titles by their search index key. Newer titles have higher keys
+    pub entry_titles: HashMap<usize, EntryTitle>,
+    /// Keyed by `Reverse` of the entry_titles key so the newest titles rank first
+    pub search_index: SearchIndex<Reverse<usize>>,
+    title_keys: HashMap<EntryTitle, usize>,
+    /// Key for the next title added to the history search
+    next_title_key: usize,
+    pub title_uses: HashMap<EntryTitle, TitleUse>,
+    /// Projects in the log, most recently used first
+    pub known_projects: Vec<String>,
+    /// Tags in the log as `key` or `key=value`, most recently used first
+    pub known_tags: Vec<String>,
+    pub completion: Option<Completion>,
     pub search_cursor: i32,
+    /// Selected row of the history search popup
+    pub history_cursor: usize,
     pub log_scroll: usize,
     pub scroll_state: ScrollbarState,
+    /// Index into current_entries of the entry highlighted in the log
+    pub selected_entry: Option<usize>,
     pub config: Config,
     log_path: String,
+    /// Modification time & length of the log when it was last read
+    log_modified: Option<(SystemTime, u64)>,
+    /// Error to show in the status line
+    pub status: Option<String>,
+    /// Date typed into the go to date prompt
+    pub date_input: Input,
+    /// Day highlighted in the date picker
+    pub picker_date: NaiveDate,
+    /// Where the last frame was drawn, for mouse clicks
+    pub areas: Areas,
+    /// Whether something changed since the last frame was drawn
+    pub redraw: bool,
+    /// Minute the last frame was drawn in
+    drawn_at: Option<NaiveDateTime>,
 }
 
 impl App {
     /// Constructs a new instance of [`App`].
-    pub fn new(log_path: String, config_path: String) -> Self {
-        Self {
+    pub fn new(log_path: String, config_path: String) -> AppResult<Self> {
+        Ok(Self {
             running: true,
             input: Input::default(),
             log_time: Default::default(),
             log_opening: Default::default(),
             log_input: Default::default(),
             log_closing: Default::default(),
+            suggestion_replaces_input: false,
             input_mode: InputMode::Logging,
+            view: View::Day,
             current_date: chrono::Local::now().date_naive(),
             current_entries: Default::default(),
+            log_index: Default::default(),
             entry_titles: Default::default(),
             search_index: Default::default(),
+            title_keys: Default::default(),
+            next_title_key: 0,
+            title_uses: Default::default(),
+            known_projects: Default::default(),
+            known_tags: Default::default(),
+            completion: None,
             search_cursor: -1,
+            history_cursor: 0,
             log_scroll: Default::default(),
             scroll_state: Default::default(),
-            config: config::read_config(config_path),
+            selected_entry: None,
+            config: config::read_config(config_path)?,
             log_path,
-        }
+            log_modified: None,
+            status: None,
+            date_input: Input::default(),
+            picker_date: chrono::Local::now().date_naive(),
+            areas: Default::default(),
+            redraw: true,
+            drawn_at: None,
+        })
     }
 
     /// Handles the tick event of the terminal.
-    pub fn tick(&self) {}
+    ///
+    /// Reloads the log if it's been changed on disk since it was last read.
+    pub fn tick(&mut self) {
+        if self.read_log_modified() != self.log_modified {
+            self.reload();
+            self.redraw = true;
+        }
+    }
+
+    /// Whether to draw a frame at `now`: after a change, or once a minute to keep
+    /// the time since the last entry current
+    pub fn needs_draw(&self, now: NaiveDateTime) -> bool {
+        self.redraw || self.drawn_at != minute_of(now)
+    }
+
+    /// Note that a frame was drawn at `now`
+    pub fn drawn(&mut self, now: NaiveDateTime) {
+        self.redraw = false;
+        self.drawn_at = minute_of(now);
+    }
 
     pub fn move_to_today(&mut self) {
         self.current_date = chrono::Local::now().naive_local().date();
@@ -97,74 +209,311 @@ impl App {
         }
     }
 
-    pub fn get_current_date_entries(
-        &mut self,
-        log_contents: &String,
-    ) -> Result<(), Box<dyn Error>> {
-        match entries::read_all_date(
-            log_contents,
-            self.current_date,
-            self.config.virtual_midnight,
-        ) {
-            Ok(c) => {
-                self.current_entries = c;
-                Ok(())
-            }
-            Err(e) => Err(e),
+    pub fn toggle_week_view(&mut self) {
+        self.view = match self.view {
+            View::Day => View::Week,
+            View::Week => View::Day,
+        };
+    }
+
+    /// Move the current date back a day, or a week in the week view
+    pub fn move_prev(&mut self) -> Result<NaiveDate, Box<dyn Error>> {
+        match self.view {
+            View::Day => self.move_prev_day(),
+            View::Week => self.move_days(-7),
         }
     }
 
-    pub fn rebuild_search_index(&mut self, log_contents: &String) {
-        let all_entries = entries::read_all_from_string(log_contents).unwrap();
-        let mut entry_titles: Vec<EntryTitle> =
-            all_entries.entries.iter().map(EntryTitle::from).collect();
+    /// Move the current date forward a day, or a week in the week view
+    pub fn move_next(&mut self) -> Result<NaiveDate, Box<dyn Error>> {
+        match self.view {
+            View::Day => self.move_next_day(),
+            View::Week => self.move_days(7),
+        }
+    }
+
+    fn move_days(&mut self, days: i64) -> Result<NaiveDate, Box<dyn Error>> {
+        let date = self
+            .current_date
+            .checked_add_signed(chrono::Duration::days(days))
+            .ok_or("Can't move that far. We're at the edge of time!")?;
+        self.current_date = date;
+        self.refresh();
+        Ok(date)
+    }
+
+    /// Each day of the week view with its entries, starting on Monday
+    pub fn week_entries(&self) -> Vec<(NaiveDate, EntryGroup)> {
+        self.current_date
+            .week(Weekday::Mon)
+            .first_day()
+            .iter_days()
+            .take(7)
+            .map(|date| (date, self.log_index.day(date)))
+            .collect()
+    }
+
+    /// Move to the closest earlier day with entries
+    pub fn move_prev_logged_day(&mut self) -> Result<NaiveDate, Box<dyn Error>> {
+        let date = self
+            .log_index
+            .prev_day(self.current_date)
+            .ok_or("No earlier days with entries")?;
+        self.current_date = date;
+        self.refresh();
+        Ok(date)
+    }
+
+    /// Move to the closest later day with entries
+    pub fn move_next_logged_day(&mut self) -> Result<NaiveDate, Box<dyn Error>> {
+        let date = self
+            .log_index
+            .next_day(self.current_date)
+            .ok_or("No later days with entries")?;
+        self.current_date = date;
+        self.refresh();
+        Ok(date)
+    }
+
+    pub fn open_date_prompt(&mut self) {
+        self.date_input.reset();
+        self.input_mode = InputMode::GoingToDate;
+    }
+
+    pub fn close_date_prompt(&mut self) {
+        self.input_mode = InputMode::Logging;
+    }
+
+    /// The date typed into the go to date prompt, if it's understood
+    pub fn date_prompt_date(&self) -> Option<NaiveDate> {
+        let value = self.date_input.value().trim();
+        if value.is_empty() {
+            return None;
+        }
+        parse::parse_date(value, chrono::Local::now().date_naive())
+    }
+
+    /// Go to the date typed into the prompt, leaving the prompt open if it isn't understood
+    pub fn submit_date_prompt(&mut self) -> Result<NaiveDate, Box<dyn Error>> {
+        let date = self
+            .date_prompt_date()
+            .ok_or(format!("Unrecognised date: {}", self.date_input.value()))?;
+        self.current_date = date;
+        self.close_date_prompt();
+        self.refresh();
+        Ok(date)
+    }
+
+    /// Show the date picker, starting at the current date
+    pub fn open_date_picker(&mut self) {
+        self.picker_date = self.current_date;
+        self.input_mode = InputMode::PickingDate;
+    }
+
+    pub fn close_date_picker(&mut self) {
+        self.input_mode = InputMode::Logging;
+    }
+
+    /// Move the date picker's highlighted day by `days`
+    pub fn move_picker(&mut self, days: i64) {
+        if let Some(date) = self
+            .picker_date
+            .checked_add_signed(chrono::Duration::days(days))
+        {
+            self.picker_date = date;
+        }
+    }
+
+    /// Move the date picker's highlighted day by `months`, clamping to the end of shorter months
+    pub fn move_picker_months(&mut self, months: i32) {
+        let date = match months < 0 {
+            true => self
+                .picker_date
+                .checked_sub_months(Months::new(months.unsigned_abs())),
+            false => self
+                .picker_date
+                .checked_add_months(Months::new(months.unsigned_abs())),
+        };
+        if let Some(date) = date {
+            self.picker_date = date;
+        }
+    }
+
+    /// Close the date picker and show `date`
+    pub fn pick_date(&mut self, date: NaiveDate) {
+        self.current_date = date;
+        self.close_date_picker();
+        self.refresh();
+    }
+
+    /// Set current_entries from the log index
+    pub fn get_current_date_entries(&mut self) {
+        self.current_entries = self.log_index.day(self.current_date);
+    }
 
-        entry_titles.reverse();
+    pub fn rebuild_search_index(&mut self) {
+        let mut entry_titles: Vec<EntryTitle> =
+            self.log_index.iter().rev().map(EntryTitle::from).collect();
 
         // Dedup while retaining order
         let mut uniques = HashSet::new();
         entry_titles.retain(|e| uniques.insert(e.clone()));
 
-        let mut search_index = SearchIndexBuilder::default()
+        self.entry_titles.clear();
+        self.title_keys.clear();
+        self.next_title_key = 0;
+        self.title_uses.clear();
+        for raw in self.log_index.iter() {
+            record_use(&mut self.title_uses, raw);
+        }
+
+        let mut projects = HashSet::new();
+        let mut tags = HashSet::new();
+        self.known_projects.clear();
+        self.known_tags.clear();
+        for raw in self.log_index.iter().rev() {
+            let project = raw.project.trim();
+            if !project.is_empty() && projects.insert(project) {
+                self.known_projects.push(project.to_string());
+            }
+            for tag in &raw.tags {
+                if tags.insert(tag) {
+                    self.known_tags.push(tag.to_string());
+                }
+            }
+        }
+        self.search_index = SearchIndexBuilder::default()
             .search_type(SearchType::Live)
             .build();
 
-        for (count, e) in entry_titles.iter().enumerate() {
-            search_index.insert(&count, &String::from(e));
+        for title in entry_titles.into_iter().rev() {
+            self.index_title(title);
+        }
+    }
+
+    /// Add a title to the history search, ranked above every title already indexed
+    fn index_title(&mut self, title: EntryTitle) {
+        if let Some(key) = self.title_keys.get(&title) {
+            self.search_index
+                .remove(&Reverse(*key), &search::fold((&title).into()));
+            self.entry_titles.remove(key);
         }
 
-        self.entry_titles = entry_titles;
-        self.search_index = search_index;
+        let key = self.next_title_key;
+        self.next_title_key += 1;
+        self.search_index
+            .insert(&Reverse(key), &search::fold((&title).into()));
+        self.entry_titles.insert(key, title.clone());
+        self.title_keys.insert(title, key);
     }
 
     /// Process [`crossterm`] input events and reconstruct [`App`] current_log
     pub fn handle_event(&mut self, evt: &CrosstermEvent) {
+        self.completion = None;
         self.input.handle_event(evt);
         self.construct_current_log();
     }
 
     pub fn cancel_search(&mut self) {
+        self.completion = None;
         self.search_cursor = -1;
         self.construct_current_log();
     }
 
     /// Override input with current history search
     pub fn accept_history(&mut self) {
-        self.input = Input::new(
-            [
-                self.log_opening.clone(),
-                self.log_input.clone(),
-                self.log_closing.clone(),
-            ]
-            .concat(),
-        );
+        self.completion = None;
+        self.input = Input::new(self.suggested_entry());
         self.search_cursor = -1;
         self.construct_current_log();
     }
 
+    /// Complete a `+tag`, or a project at the start of the input, from the ones in the log.
+    /// Repeating cycles through the candidates.
+    ///
+    /// Returns false if there's nothing to complete
+    pub fn complete(&mut self) -> bool {
+        if self.search_cursor >= 0 {
+            return false;
+        }
+        if let Some(completion) = &mut self.completion {
+            completion.index = (completion.index + 1) % completion.candidates.len();
+            self.apply_completion();
+            return true;
+        }
+
+        let value = self.input.value();
+        let cursor = value
+            .char_indices()
+            .nth(self.input.cursor())
+            .map_or(value.len(), |(i, _)| i);
+        let before = &value[..cursor];
+        let word_start = before
+            .char_indices()
+            .rev()
+            .find(|(_, c)| c.is_whitespace())
+            .map_or(0, |(i, c)| i + c.len_utf8());
+        let word = &before[word_start..];
+
+        let (kind, start, typed) = if let Some(typed) = word.strip_prefix('+') {
+            (CompletionKind::Tag, word_start + 1, typed)
+        } else if word_start == 0 && !word.is_empty() && !word.contains(':') {
+            (CompletionKind::Project, 0, word)
+        } else {
+            return false;
+        };
+        let known = match kind {
+            CompletionKind::Project => &self.known_projects,
+            CompletionKind::Tag => &self.known_tags,
+        };
+        let typed = typed.to_lowercase();
+        let candidates: Vec<String> = known
+            .iter()
+            .filter(|c| c.to_lowercase().starts_with(&typed))
+            .cloned()
+            .collect();
+        if candidates.is_empty() {
+            return false;
+        }
+
+        self.completion = Some(Completion {
+            kind,
+            candidates,
+            index: 0,
+            start,
+            end: cursor,
+        });
+        self.apply_completion();
+        true
+    }
+
+    /// Replace the completed word with the current candidate
+    fn apply_completion(&mut self) {
+        let Some(completion) = &mut self.completion else {
+            return;
+        };
+        let value = self.input.value();
+        let rest = &value[completion.end..];
+        let mut text = completion.candidates[completion.index].clone();
+        // Only add what's missing of the ": " after a project
+        if completion.kind == CompletionKind::Project && !rest.trim_start().starts_with(':') {
+            match rest.starts_with(char::is_whitespace) {
+                true => text.push(':'),
+                false => text.push_str(": "),
+            }
+        }
+
+        let new_value = [&value[..completion.start], &text, rest].concat();
+        completion.end = completion.start + text.len();
+        let cursor = new_value[..completion.end].chars().count();
+        self.input = Input::new(new_value).with_cursor(cursor);
+        self.construct_current_log();
+    }
+
     /// If current entry is empty, move input to editing time
     pub fn handle_backspace_into_time(&mut self) {
         if !self.log_time.is_empty() && self.log_input.is_empty() {
+            self.completion = None;
             self.log_input = self.log_time.clone();
             self.log_time.clear();
             self.input = Input::new(self.log_input.clone());
@@ -174,28 +523,38 @@ impl App {
     /// Construct the current log text from input and the search index if applicable
     fn construct_current_log(&mut self) {
         let time = chrono::Local::now().naive_local().time();
-        let (input_time, input_entry) = entries::split_time_and_entry(
-            self.input.value().to_string(),
+        // Parse with any time already taken from the input so a duration can follow it
+        let (input_start, input_time, input_entry) = parse::split_range_and_entry(
+            [self.log_time.as_str(), self.input.value()].concat(),
             NaiveDateTime::new(self.current_date, time),
         );
 
         self.log_input = input_entry.clone();
 
-        if let Some(time_string) = input_time.map(|t| t.format("%H:%M").to_string()) {
+        // Show the resolved time, with its date if it isn't on the current day
+        let first = input_start.or(input_time);
+        if let (Some(first), Some(end)) = (first, input_time) {
+            let mut time_string = match first.date() == self.current_date {
+                true => first.format("%H:%M").to_string(),
+                false => first.format("%Y-%m-%d %H:%M").to_string(),
+            };
+            if input_start.is_some() {
+                time_string.push_str(&end.format("-%H:%M").to_string());
+            }
             self.log_time = [time_string, String::from(" ")].concat();
-            self.input = Input::new(input_entry.clone());
+            if input_entry != self.input.value() {
+                // The time moved out of the input, so a completion's range is stale
+                self.completion = None;
+                self.input = Input::new(input_entry.clone());
+            }
         }
 
-        let match_results = if input_entry.is_empty() {
-            self.search_index
-                .search(self.search_index.dump_keyword().unwrap())
-        } else {
-            self.search_index.search(&input_entry)
-        };
+        let match_results = self.search(&input_entry, chrono::Local::now().naive_local());
 
         if self.search_cursor < 0 || match_results.is_empty() {
             self.log_opening.clear();
             self.log_closing.clear();
+            self.suggestion_replaces_input = false;
         } else {
             let index = if self.search_cursor >= match_results.len() as i32 {
                 self.search_cursor = (match_results.len() - 1) as i32;
@@ -204,48 +563,248 @@ impl App {
                 self.search_cursor as usize
             };
 
-            let history: String = self.entry_titles.get(*match_results[index]).unwrap().into();
-            let start = history.find(self.log_input.as_str()).unwrap_or_default();
-
-            let (opening, mid) = history.split_at(start);
-            let (_, closing) = mid.split_at(self.log_input.len());
-
-            self.log_opening = String::from(opening);
-            self.log_closing = String::from(closing);
+            let history: String = self.entry_titles.get(&match_results[index]).unwrap().into();
+            // The index matches words anywhere in the title, not just the input as a whole
+            match search::find_folded(&history, &self.log_input) {
+                Some(matched) => {
+                    self.log_opening = String::from(&history[..matched.start]);
+                    self.log_closing = String::from(&history[matched.end..]);
+                    self.suggestion_replaces_input = false;
+                }
+                None => {
+                    self.log_opening.clear();
+                    self.log_closing = history;
+                    self.suggestion_replaces_input = true;
+                }
+            }
         }
     }
 
-    /// Construct a new Entry from [`App`] current_log, save it to disk, and add it to the current list
-    pub fn commit_current_log(&self) -> Result<(), Box<dyn Error>> {
-        let time = chrono::Local::now().naive_local().time();
-        let entry = EntryRaw::from_string(
-            [
-                self.log_time.clone(),
-                self.log_opening.clone(),
-                self.log_input.clone(),
-                self.log_closing.clone(),
+    /// The entry with any history suggestion applied, without the time
+    fn suggested_entry(&self) -> String {
+        match self.suggestion_replaces_input {
+            true => self.log_closing.clone(),
+            false => [
+                self.log_opening.as_str(),
+                self.log_input.as_str(),
+                self.log_closing.as_str(),
             ]
             .concat(),
-            NaiveDateTime::new(self.current_date, time),
-        );
-        entries::write(self, entry)
+        }
     }
 
-    /// Construct a new [`EntryRaw`], save it to disk, and add it to the current list
-    pub fn add_log(&self, input: String) -> Result<(), Box<dyn Error>> {
+    /// Construct a new Entry from [`App`] current_log, save it to disk, and add it to the current list.
+    pub fn commit_current_log(&mut self) -> Result<(), Box<dyn Error>> {
+        self.add_log([self.log_time.clone(), self.suggested_entry()].concat())
+    }
+
+    /// Construct a new [`EntryRaw`], save it to disk, and add it to the current list.
+    pub fn add_log(&mut self, input: String) -> Result<(), Box<dyn Error>> {
         let time = chrono::Local::now().naive_local().time();
-        let entry = EntryRaw::from_string(input, NaiveDateTime::new(self.current_date, time));
-        entries::write(self, entry)
+        let parsed = parse::parse_entry(&input, NaiveDateTime::new(self.current_date, time))?;
+        match parsed.start {
+            Some(start) => self.write_range(start, parsed.entry),
+            None => self.write_entries(vec![parsed.entry]),
+        }
+    }
+
+    /// Save an entry that starts at `start`, along with a filler entry ending at `start`
+    /// unless the previous entry already ends there.
+    ///
+    /// Refuses entries that overlap ones already in the log, since the filler would take
+    /// over the start of the entry it's inside, and entries crossing virtual midnight, since
+    /// the entry would start the later day and its time would go to the filler
+    fn write_range(&mut self, start: NaiveDateTime, entry: EntryRaw) -> Result<(), Box<dyn Error>> {
+        let virtual_midnight = self.config.virtual_midnight;
+        if entries::effective_date(start, virtual_midnight)
+            != entry.effective_date(virtual_midnight)
+        {
+            return Err(format!(
+                "Not logged, it crosses virtual midnight ({}). Log each day's part separately",
+                virtual_midnight.format("%H:%M")
+            )
+            .into());
+        }
+
+        // Held from reading the log until the entries are written so the overlap check
+        // can't go stale
+        let lock = LogLock::exclusive(&self.log_path())?;
+        self.load_index()?;
+
+        let overlaps: Vec<String> = self
+            .log_index
+            .overlapping(start, entry.end)
+            .iter()
+            .map(|e| {
+                format!(
+                    "{} ({}-{})",
+                    e.display_sans_time(),
+                    e.start.format("%H:%M"),
+                    e.end.format("%H:%M")
+                )
+            })
+            .collect();
+
+        if !overlaps.is_empty() {
+            return Err(format!("Not logged, it overlaps {}", overlaps.join(", ")).into());
+        }
+
+        let mut rows = vec![];
+        if self.log_index.last_before(start).map(|e| e.end) != Some(start) {
+            rows.push(EntryRaw::filler(start, &self.config.filler));
+        }
+        rows.push(entry);
+        self.write_entries_locked(lock, rows)
+    }
+
+    /// Read the log into the log index if it's changed since it was last read.
+    ///
+    /// The caller must hold the log's [`LogLock`]
+    fn load_index(&mut self) -> Result<(), AppError> {
+        let modified = self.read_log_modified();
+        if modified != self.log_modified {
+            let contents = self.read_log()?;
+            self.log_index = LogIndex::from_log(&contents, self.config.virtual_midnight)
+                .map_err(|e| e.in_file(&self.log_path()))?;
+            self.log_modified = modified;
+            self.get_current_date_entries();
+            self.rebuild_search_index();
+        }
+        Ok(())
+    }
+
+    /// Save entries to disk in one write and add them to the log index without rereading the log
+    fn write_entries(&mut self, entries: Vec<EntryRaw>) -> Result<(), Box<dyn Error>> {
+        let lock = LogLock::exclusive(&self.log_path())?;
+        self.write_entries_locked(lock, entries)
+    }
+
+    /// [`App::write_entries`] for callers already holding the log's exclusive `lock`.
+    ///
+    /// It's held from checking the log is unchanged until its new modification time is
+    /// read, so no other write can slip in unnoticed
+    fn write_entries_locked(
+        &mut self,
+        lock: LogLock,
+        entries: Vec<EntryRaw>,
+    ) -> Result<(), Box<dyn Error>> {
+        let loaded = self.log_modified.is_some();
+        let in_sync = loaded && self.read_log_modified() == self.log_modified;
+
+        entries::write_locked(self, &entries)?;
+
+        if in_sync {
+            self.log_modified = self.read_log_modified();
+            drop(lock);
+            for entry in entries {
+                self.index_title(EntryTitle::from(&entry));
+                record_use(&mut self.title_uses, &entry);
+                remember(&mut self.known_projects, entry.project.trim());
+                for tag in &entry.tags {
+                    remember(&mut self.known_tags, &tag.to_string());
+                }
+                self.log_index.insert(entry);
+            }
+            self.get_current_date_entries();
+        } else if loaded {
+            drop(lock);
+            // Someone else changed the log since we read it
+            self.reload();
+        }
+        Ok(())
+    }
+
+    /// Keys into entry_titles of the titles matching `query`, ignoring case and accents.
+    /// Every title matches an empty query.
+    ///
+    /// Ranked by [`TitleUse::score`] at `now`, newest first when scores tie
+    fn search(&self, query: &str, now: NaiveDateTime) -> Vec<usize> {
+        let query = search::fold(query);
+        let results = if query.is_empty() {
+            self.search_index
+                .search(self.search_index.dump_keyword().unwrap())
+        } else {
+            self.search_index.search(&query)
+        };
+
+        let mut ranked: Vec<(usize, f64)> = results
+            .into_iter()
+            .map(|key| {
+                let score = self
+                    .entry_titles
+                    .get(&key.0)
+                    .and_then(|title| self.title_uses.get(title))
+                    .map_or(0.0, |u| u.score(now));
+                (key.0, score)
+            })
+            .collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        ranked.into_iter().map(|(i, _)| i).collect()
+    }
+
+    /// Titles matching the input for the history search popup, with how they've been used,
+    /// ranked at `now`
+    pub fn history_matches(&self, now: NaiveDateTime) -> Vec<(&EntryTitle, Option<&TitleUse>)> {
+        self.search(&self.log_input, now)
+            .into_iter()
+            .filter_map(|i| self.entry_titles.get(&i))
+            .map(|title| (title, self.title_uses.get(title)))
+            .collect()
+    }
+
+    pub fn open_history_search(&mut self) {
+        self.cancel_search();
+        self.history_cursor = 0;
+        self.input_mode = InputMode::SearchingHistory;
+    }
+
+    pub fn close_history_search(&mut self) {
+        self.input_mode = InputMode::Logging;
+    }
+
+    /// Move the history popup's selection by `rows`, staying within the matches
+    pub fn move_history_cursor(&mut self, rows: i32) {
+        let now = chrono::Local::now().naive_local();
+        let last = self.history_matches(now).len().saturating_sub(1);
+        let cursor = match rows < 0 {
+            true => self
+                .history_cursor
+                .saturating_sub(rows.unsigned_abs() as usize),
+            false => self.history_cursor.saturating_add(rows as usize),
+        };
+        self.history_cursor = cursor.min(last);
+    }
+
+    /// Replace the input with the selected history popup match and close the popup
+    pub fn accept_history_match(&mut self) {
+        let title = self
+            .history_matches(chrono::Local::now().naive_local())
+            .get(self.history_cursor)
+            .map(|(title, _)| String::from(*title));
+        if let Some(title) = title {
+            self.completion = None;
+            self.input = Input::new(title);
+            self.construct_current_log();
+        }
+        self.close_history_search();
+    }
+
+    /// Update the input from a key typed into the history popup, selecting the first match
+    pub fn handle_history_event(&mut self, evt: &CrosstermEvent) {
+        self.handle_event(evt);
+        self.history_cursor = 0;
     }
 
     /// Move the history search cursor back one entry and reconstruct [`App`] current_log
     pub fn search_back(&mut self) {
+        self.completion = None;
         self.search_cursor = self.search_cursor.saturating_add(1);
         self.construct_current_log();
     }
 
     /// Move the history search cursor forward one entry and reconstruct [`App`] current_log
     pub fn search_forward(&mut self) {
+        self.completion = None;
         let temp = self.search_cursor - 1;
         if temp < -1 {
             self.search_cursor = -1;
@@ -265,21 +824,69 @@ impl App {
     }
 
     /// Get content of the log file as a [`String`]
-    pub fn log_contents(&self) -> String {
-        std::fs::read_to_string(self.log_path()).unwrap()
+    pub fn log_contents(&self) -> Result<String, AppError> {
+        let log_path = self.log_path();
+        let _lock = LogLock::shared(&log_path).map_err(|cause| AppError::Io {
+            file: log_path.clone(),
+            cause,
+        })?;
+        self.read_log()
+    }
+
+    /// Read the log without locking it, for callers already holding its [`LogLock`]
+    fn read_log(&self) -> Result<String, AppError> {
+        let log_path = self.log_path();
+        std::fs::read_to_string(&log_path).map_err(|cause| AppError::Io {
+            file: log_path,
+            cause,
+        })
     }
 
-    /// Reset input, reload entries from disk, & rebuild the search index
+    /// Reset input & show the current date's entries, reloading from disk if the log changed
     pub fn refresh(&mut self) {
+        self.status = None;
+        self.completion = None;
         self.search_cursor = -1;
         self.log_time.clear();
         self.log_opening.clear();
         self.log_input.clear();
         self.log_closing.clear();
+        self.suggestion_replaces_input = false;
         self.input.reset();
-        self.get_current_date_entries(&self.log_contents());
+        self.selected_entry = None;
+        if self.read_log_modified() != self.log_modified {
+            self.reload();
+        } else {
+            self.get_current_date_entries();
+        }
         self.scroll_log(0);
-        self.rebuild_search_index(&self.log_contents());
+    }
+
+    /// Reload entries from disk & rebuild the search index, keeping any input
+    pub fn reload(&mut self) {
+        self.log_modified = self.read_log_modified();
+        match self.log_contents().and_then(|contents| {
+            LogIndex::from_log(&contents, self.config.virtual_midnight)
+                .map_err(|e| e.in_file(&self.log_path()))
+        }) {
+            Ok(index) => {
+                self.log_index = index;
+                self.status = None;
+            }
+            Err(e) => self.status = Some(e.to_string()),
+        }
+        self.get_current_date_entries();
+        self.rebuild_search_index();
+
+        let len = self.current_entries.len();
+        self.selected_entry = self.selected_entry.filter(|i| *i < len);
+        self.scroll_log(self.log_scroll.min(len.saturating_sub(1)));
+        self.construct_current_log();
+    }
+
+    fn read_log_modified(&self) -> Option<(SystemTime, u64)> {
+        let metadata = std::fs::metadata(self.log_path()).ok()?;
+        Some((metadata.modified().ok()?, metadata.len()))
     }
 
     pub fn scroll_log_up(&mut self) {
@@ -301,8 +908,450 @@ impl App {
         self.scroll_state = self.scroll_state.position(index);
     }
 
+    /// Select the previous entry in the log, starting from the latest
+    pub fn select_prev_entry(&mut self) {
+        if self.current_entries.is_empty() {
+            return;
+        }
+        self.selected_entry = Some(match self.selected_entry {
+            Some(i) => i.saturating_sub(1),
+            None => self.current_entries.len() - 1,
+        });
+    }
+
+    /// Select the next entry in the log, clearing the selection past the latest
+    pub fn select_next_entry(&mut self) {
+        self.selected_entry = match self.selected_entry {
+            Some(i) if i + 1 < self.current_entries.len() => Some(i + 1),
+            _ => None,
+        };
+    }
+
+    /// Select an entry by its index, ignoring indexes past the last entry
+    pub fn select_entry(&mut self, index: usize) {
+        if index < self.current_entries.len() {
+            self.selected_entry = Some(index);
+        }
+    }
+
+    pub fn selected_entry(&self) -> Option<&Entry> {
+        self.selected_entry
+            .and_then(|i| self.current_entries.entries.get(i))
+    }
+
     /// Set running to false to quit the application.
     pub fn quit(&mut self) {
         self.running = false;
     }
 }
+
+fn minute_of(datetime: NaiveDateTime) -> Option<NaiveDateTime> {
+    datetime.with_second(0)?.with_nanosecond(0)
+}
+
+/// Move `item` to the front of a most recently used list
+fn remember(list: &mut Vec<String>, item: &str) {
+    if item.is_empty() {
+        return;
+    }
+    list.retain(|i| i != item);
+    list.insert(0, item.to_string());
+}
+
+/// Count a use of the entry's title
+fn record_use(uses: &mut HashMap<EntryTitle, TitleUse>, entry: &EntryRaw) {
+    uses.entry(EntryTitle::from(entry))
+        .and_modify(|u| u.record(entry.end))
+        .or_insert_with(|| TitleUse::new(entry.end));
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+
+    use crossterm::event::{Event as CrosstermEvent, KeyCode, KeyEvent};
+    use tui_input::Input;
+
+    use super::{App, InputMode};
+    use crate::testing::TempLog;
+
+    #[test]
+    fn test_tick_reloads_and_keeps_input() {
+        let log = TempLog::new("2023-06-14 09:00, , **arrive, \n");
+        let mut app = log.app();
+        app.current_date = chrono::NaiveDate::from_ymd_opt(2023, 6, 14).unwrap();
+        app.refresh();
+        app.input = Input::new(String::from("PROJ: half typed"));
+        assert_eq!(app.current_entries.len(), 1);
+
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&log.path)
+            .unwrap()
+            .write_all(b"2023-06-14 10:00, PROJ, work, \n")
+            .unwrap();
+        app.tick();
+
+        assert_eq!(app.current_entries.len(), 2);
+        assert_eq!(app.input.value(), "PROJ: half typed");
+    }
+
+    #[test]
+    fn test_add_log_updates_index() {
+        let log = TempLog::new("2023-06-14 09:00, , **arrive, \n");
+        let mut app = log.app();
+        app.current_date = chrono::NaiveDate::from_ymd_opt(2023, 6, 14).unwrap();
+        app.refresh();
+        app.add_log(String::from("10:00 PROJ: work")).unwrap();
+
+        assert_eq!(app.current_entries.len(), 2);
+        let newest = app.search_index.search("work");
+        assert_eq!(app.entry_titles[&newest[0].0].to_string(), "PROJ: work");
+        assert_eq!(
+            app.entry_titles[&app.search_index.search("arrive")[0].0].to_string(),
+            "**arrive"
+        );
+
+        // Logging a title again moves it rather than adding another
+        app.add_log(String::from("11:00 **arrive")).unwrap();
+        assert_eq!(app.entry_titles.len(), 2);
+        assert_eq!(
+            app.entry_titles[&app.search_index.search("arrive")[0].0].to_string(),
+            "**arrive"
+        );
+    }
+
+    #[test]
+    fn test_add_range_fills_gap_and_refuses_overlap() {
+        let log = TempLog::new("2023-06-14 09:00, , **arrive, \n2023-06-14 12:00, PROJ, work, \n");
+        let mut app = log.app();
+        app.current_date = chrono::NaiveDate::from_ymd_opt(2023, 6, 14).unwrap();
+        // Not refreshed first, like the add subcommand
+        app.add_log(String::from("13:00-14:00 PROJ: review"))
+            .unwrap();
+        let overlap = app
+            .add_log(String::from("10:00-10:30 MEET: standup"))
+            .unwrap_err();
+        app.add_log(String::from("14:00-15:00 PROJ: docs")).unwrap();
+        let contents = log.contents();
+
+        assert_eq!(
+            overlap.to_string(),
+            "Not logged, it overlaps PROJ: work (09:00-12:00)"
+        );
+        let entries: Vec<String> = app
+            .current_entries
+            .entries
+            .iter()
+            .map(|e| e.to_string())
+            .collect();
+        assert_eq!(
+            entries,
+            vec![
+                "         09:00  **arrive",
+                "3h 0m    12:00  PROJ: work",
+                "1h 0m    13:00  **untracked",
+                "1h 0m    14:00  PROJ: review",
+                "1h 0m    15:00  PROJ: docs",
+            ]
+        );
+        assert_eq!(contents.matches("**untracked").count(), 1);
+        assert!(!contents.contains("standup"));
+    }
+
+    #[test]
+    fn test_add_range_refuses_crossing_virtual_midnight() {
+        let log = TempLog::new("2023-06-14 09:00, , **arrive, \n2023-06-14 23:00, PROJ, work, \n");
+        let mut app = log.app();
+        app.current_date = chrono::NaiveDate::from_ymd_opt(2023, 6, 14).unwrap();
+
+        let crossing = app
+            .add_log(String::from("2023-06-15 01:00-03:00 PROJ: late"))
+            .unwrap_err();
+        assert_eq!(
+            crossing.to_string(),
+            "Not logged, it crosses virtual midnight (02:00). Log each day's part separately"
+        );
+        assert!(!log.contents().contains("late"));
+
+        // Up to virtual midnight is still the same day
+        app.add_log(String::from("2023-06-15 00:00-01:30 PROJ: late"))
+            .unwrap();
+        let entries: Vec<String> = app
+            .current_entries
+            .entries
+            .iter()
+            .map(|e| e.to_string())
+            .collect();
+        assert_eq!(
+            entries[2..],
+            ["1h 0m    00:00  **untracked", "1h 30m   01:30  PROJ: late"]
+        );
+    }
+
+    #[test]
+    fn test_add_duration_refuses_crossing_virtual_midnight() {
+        let log = TempLog::new("2023-06-14 09:00, , **arrive, \n2023-06-14 23:00, PROJ, work, \n");
+        let mut app = log.app();
+        app.config.virtual_midnight = chrono::NaiveTime::from_hms_opt(3, 0, 0).unwrap();
+        app.current_date = chrono::NaiveDate::from_ymd_opt(2023, 6, 15).unwrap();
+
+        // Starts at 02:35, still the 14th
+        let crossing = app.add_log(String::from("03:20 45m PROJ: x")).unwrap_err();
+        assert_eq!(
+            crossing.to_string(),
+            "Not logged, it crosses virtual midnight (03:00). Log each day's part separately"
+        );
+        assert!(!log.contents().contains("PROJ,x"));
+
+        app.add_log(String::from("02:50 45m PROJ: y")).unwrap();
+        assert!(log
+            .contents()
+            .ends_with("2023-06-15 02:05,,**untracked,,\n2023-06-15 02:50,PROJ,y,,\n"));
+    }
+
+    #[test]
+    fn test_duration_after_time_shows_range() {
+        let mut app = App::new(
+            String::from("./test/test.csv"),
+            String::from("./test/config.toml"),
+        )
+        .unwrap();
+        app.current_date = chrono::NaiveDate::from_ymd_opt(2023, 6, 14).unwrap();
+        app.refresh();
+
+        for c in "17:00 45m PROJ".chars() {
+            app.handle_event(&CrosstermEvent::Key(KeyEvent::from(KeyCode::Char(c))));
+        }
+
+        assert_eq!(app.log_time, "16:15-17:00 ");
+        assert_eq!(app.input.value(), "PROJ");
+    }
+
+    #[test]
+    fn test_week_view() {
+        let mut app = App::new(
+            String::from("./test/test.csv"),
+            String::from("./test/config.toml"),
+        )
+        .unwrap();
+        app.current_date = chrono::NaiveDate::from_ymd_opt(2023, 6, 14).unwrap();
+        app.refresh();
+        app.toggle_week_view();
+
+        let week = app.week_entries();
+        let lengths: Vec<usize> = week.iter().map(|(_, group)| group.len()).collect();
+        assert_eq!(
+            week[0].0,
+            chrono::NaiveDate::from_ymd_opt(2023, 6, 12).unwrap()
+        );
+        assert_eq!(lengths, vec![0, 0, 5, 4, 0, 0, 0]);
+
+        app.move_next().unwrap();
+        assert_eq!(
+            app.current_date,
+            chrono::NaiveDate::from_ymd_opt(2023, 6, 21).unwrap()
+        );
+        app.toggle_week_view();
+        app.move_prev().unwrap();
+        assert_eq!(
+            app.current_date,
+            chrono::NaiveDate::from_ymd_opt(2023, 6, 20).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_go_to_date_and_logged_days() {
+        let mut app = App::new(
+            String::from("./test/test.csv"),
+            String::from("./test/config.toml"),
+        )
+        .unwrap();
+        app.current_date = chrono::NaiveDate::from_ymd_opt(2023, 7, 1).unwrap();
+        app.refresh();
+
+        app.open_date_prompt();
+        app.date_input = Input::new(String::from("someday"));
+        assert!(app.submit_date_prompt().is_err());
+        assert!(matches!(app.input_mode, InputMode::GoingToDate));
+        app.date_input = Input::new(String::from("2023-06-01"));
+        app.submit_date_prompt().unwrap();
+        assert!(matches!(app.input_mode, InputMode::Logging));
+        assert_eq!(
+            app.current_date,
+            chrono::NaiveDate::from_ymd_opt(2023, 6, 1).unwrap()
+        );
+
+        assert!(app.move_prev_logged_day().is_err());
+        app.move_next_logged_day().unwrap();
+        app.move_next_logged_day().unwrap();
+        assert_eq!(
+            app.current_date,
+            chrono::NaiveDate::from_ymd_opt(2023, 6, 15).unwrap()
+        );
+        assert_eq!(app.current_entries.len(), 4);
+        assert!(app.move_next_logged_day().is_err());
+    }
+
+    #[test]
+    fn test_history_search_popup() {
+        let mut app = App::new(
+            String::from("./test/test.csv"),
+            String::from("./test/config.toml"),
+        )
+        .unwrap();
+        app.refresh();
+        app.open_history_search();
+        for c in "PROJ".chars() {
+            app.handle_history_event(&CrosstermEvent::Key(KeyEvent::from(KeyCode::Char(c))));
+        }
+
+        let matches: Vec<(String, usize)> = app
+            .history_matches(chrono::Local::now().naive_local())
+            .iter()
+            .map(|(title, title_use)| (String::from(*title), title_use.unwrap().count))
+            .collect();
+        assert_eq!(matches, vec![(String::from("PROJ: work"), 4)]);
+
+        app.move_history_cursor(5);
+        assert_eq!(app.history_cursor, 0);
+        app.accept_history_match();
+        assert!(matches!(app.input_mode, InputMode::Logging));
+        assert_eq!(app.input.value(), "PROJ: work");
+    }
+
+    #[test]
+    fn test_complete_projects_and_tags() {
+        let mut app = App::new(
+            String::from("./test/test.csv"),
+            String::from("./test/config.toml"),
+        )
+        .unwrap();
+        app.refresh();
+        app.known_tags = vec![String::from("bug"), String::from("build")];
+        let type_str = |app: &mut App, text: &str| {
+            for c in text.chars() {
+                app.handle_event(&CrosstermEvent::Key(KeyEvent::from(KeyCode::Char(c))));
+            }
+        };
+
+        type_str(&mut app, "pr");
+        assert!(app.complete());
+        assert_eq!(app.input.value(), "PROJ: ");
+        type_str(&mut app, "new thing +b");
+        assert!(app.complete());
+        assert_eq!(app.input.value(), "PROJ: new thing +bug");
+        assert!(app.complete());
+        assert_eq!(app.input.value(), "PROJ: new thing +build");
+        assert!(app.complete());
+        assert_eq!(app.input.value(), "PROJ: new thing +bug");
+
+        // Activities aren't completed
+        type_str(&mut app, " more");
+        assert!(!app.complete());
+        assert!(app.completion.is_none());
+
+        // Completing a project before the activity only adds the missing ':'
+        app.refresh();
+        app.input = Input::new(String::from("P meeting")).with_cursor(1);
+        assert!(app.complete());
+        assert_eq!(app.input.value(), "PROJ: meeting");
+        assert!(app.complete());
+        assert_eq!(app.input.value(), "PROJ: meeting");
+
+        // Replacing the input from history drops the completion and its stale range
+        app.refresh();
+        type_str(&mut app, "p");
+        assert!(app.complete());
+        app.search_back();
+        assert!(app.completion.is_none());
+        app.accept_history();
+        app.input = Input::new(String::from("p"));
+        assert!(app.complete());
+        assert_eq!(app.input.value(), "PROJ: ");
+    }
+
+    #[test]
+    fn test_history_ranking_and_folding() {
+        let log = TempLog::new(
+            "2023-06-14 09:00, , **arrive, \n\
+             2023-06-14 10:00, PROJ, Café planning, \n\
+             2023-06-14 11:00, PROJ, Café planning, \n\
+             2023-06-14 12:00, PROJ, Café planning, \n\
+             2023-06-14 13:00, PROJ, cake, \n",
+        );
+        let mut app = log.app();
+        app.refresh();
+
+        for c in "proj ca".chars() {
+            app.handle_event(&CrosstermEvent::Key(KeyEvent::from(KeyCode::Char(c))));
+        }
+        // An hour after the last entry, so the newest title is also the most recent
+        let now = chrono::NaiveDate::from_ymd_opt(2023, 6, 14)
+            .unwrap()
+            .and_hms_opt(14, 0, 0)
+            .unwrap();
+        let titles: Vec<String> = app
+            .history_matches(now)
+            .iter()
+            .map(|(title, _)| String::from(*title))
+            .collect();
+        // The most used title ranks above the newest
+        assert_eq!(titles, vec!["PROJ: Café planning", "PROJ: cake"]);
+
+        app.input = Input::new(String::from("cafe"));
+        app.search_back();
+        assert_eq!(app.log_opening, "PROJ: ");
+        assert_eq!(app.log_closing, " planning");
+
+        // Words matched apart replace the input with the title
+        app.input = Input::new(String::from("proj ca"));
+        app.search_back();
+        assert!(app.suggestion_replaces_input);
+        // Either title, since the suggestion is ranked at the current time
+        let title = app.log_closing.clone();
+        assert!(titles.contains(&title));
+        app.accept_history();
+        assert_eq!(app.input.value(), title);
+        assert!(!app.suggestion_replaces_input);
+
+        app.input = Input::new(String::from("plan pro"));
+        app.search_back();
+        app.commit_current_log().unwrap();
+        assert!(log.contents().ends_with(",PROJ,Café planning,,\n"));
+    }
+
+    #[test]
+    fn test_redraw_each_minute() {
+        let mut app = App::new(
+            String::from("./test/test.csv"),
+            String::from("./test/config.toml"),
+        )
+        .unwrap();
+        app.current_date = chrono::NaiveDate::from_ymd_opt(2023, 6, 14).unwrap();
+        app.refresh();
+        let date = app.current_date;
+        let at = |h: u32, m: u32, s: u32| date.and_hms_opt(h, m, s).unwrap();
+
+        assert!(app.needs_draw(at(18, 0, 5)));
+        app.drawn(at(18, 0, 5));
+        assert!(!app.needs_draw(at(18, 0, 59)));
+        assert!(app.needs_draw(at(18, 1, 0)));
+        app.redraw = true;
+        assert!(app.needs_draw(at(18, 0, 30)));
+
+        assert_eq!(
+            app.current_entries
+                .time_since_last_display(at(18, 0, 0))
+                .as_deref(),
+            Some("0h 45m")
+        );
+        // Counts across midnight
+        assert_eq!(
+            app.current_entries
+                .time_since_last_display(at(17, 15, 0) + chrono::Duration::days(1))
+                .as_deref(),
+            Some("24h 0m")
+        );
+    }
+}
@@ -0,0 +1,159 @@
+use std::{
+    error::Error,
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+use similar::TextDiff;
+
+use crate::lock::{self, LogLock};
+
+/// Directory the log's backups are kept in, a `backups` folder next to the log
+pub fn dir(log_path: &Path) -> PathBuf {
+    log_path
+        .parent()
+        .unwrap_or_else(|| Path::new(""))
+        .join("backups")
+}
+
+/// Copy the log into `dir` as a timestamped backup, then remove all but the newest `keep` backups.
+///
+/// Does nothing if `keep` is 0.
+pub fn create(dir: &Path, log_path: &Path, keep: usize) -> Result<(), Box<dyn Error>> {
+    if keep == 0 {
+        return Ok(());
+    }
+
+    copy(dir, log_path)?;
+    rotate(dir, log_path, keep)
+}
+
+fn copy(dir: &Path, log_path: &Path) -> Result<(), Box<dyn Error>> {
+    std::fs::create_dir_all(dir)?;
+    let mut name = backup_prefix(log_path);
+    name.push(
+        chrono::Local::now()
+            .format("%Y%m%d-%H%M%S%.3f.bak")
+            .to_string(),
+    );
+    std::fs::copy(log_path, dir.join(name))?;
+    Ok(())
+}
+
+/// Remove all but the newest `keep` backups
+fn rotate(dir: &Path, log_path: &Path, keep: usize) -> Result<(), Box<dyn Error>> {
+    for old in list(dir, log_path)?.iter().skip(keep) {
+        std::fs::remove_file(old)?;
+    }
+    Ok(())
+}
+
+/// Backups of the log in `dir`, newest first
+pub fn list(dir: &Path, log_path: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+
+    let prefix = backup_prefix(log_path);
+    let prefix = prefix.to_string_lossy();
+    let mut backups: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.file_name()
+                .map(|name| name.to_string_lossy())
+                .map_or(false, |name| {
+                    name.starts_with(prefix.as_ref()) && name.ends_with(".bak")
+                })
+        })
+        .collect();
+
+    // Timestamps sort lexically
+    backups.sort();
+    backups.reverse();
+    Ok(backups)
+}
+
+/// Unified diff from the backup to the current log
+pub fn diff(backup: &Path, log_path: &Path) -> Result<String, Box<dyn Error>> {
+    let old = std::fs::read_to_string(backup)?;
+    let new = std::fs::read_to_string(log_path)?;
+
+    Ok(TextDiff::from_lines(&old, &new)
+        .unified_diff()
+        .header(&backup.to_string_lossy(), &log_path.to_string_lossy())
+        .to_string())
+}
+
+/// Replace the log with a backup, backing up the current log first.
+///
+/// The current log is backed up even if `keep` is 0, so a restore can always be undone
+pub fn restore(
+    dir: &Path,
+    backup: &Path,
+    log_path: &Path,
+    keep: usize,
+) -> Result<(), Box<dyn Error>> {
+    let _lock = LogLock::exclusive(log_path)?;
+    // Copy before backing up the current log in case rotation removes it
+    let temp_path = lock::temp_path(log_path);
+    std::fs::copy(backup, &temp_path)?;
+    copy(dir, log_path)?;
+    if keep > 0 {
+        rotate(dir, log_path, keep)?;
+    }
+    std::fs::rename(temp_path, log_path)?;
+    Ok(())
+}
+
+fn backup_prefix(log_path: &Path) -> OsString {
+    let mut prefix = log_path.file_name().unwrap_or_default().to_owned();
+    prefix.push(".");
+    prefix
+}
+
+#[cfg(test)]
+mod test {
+    use super::{create, list, restore};
+    use crate::testing::TempLog;
+
+    #[test]
+    fn test_rotate_and_restore() {
+        let log = TempLog::new("");
+        let dir = log.dir.join("backups");
+        let log_path = log.path.clone();
+
+        for i in 0..4 {
+            std::fs::write(&log_path, format!("version {}", i)).unwrap();
+            create(&dir, &log_path, 3).unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(2));
+        }
+        let backups = list(&dir, &log_path).unwrap();
+        assert_eq!(backups.len(), 3);
+        assert_eq!(std::fs::read_to_string(&backups[0]).unwrap(), "version 3");
+
+        std::fs::write(&log_path, "oops").unwrap();
+        restore(&dir, &backups[2], &log_path, 3).unwrap();
+        let restored = std::fs::read_to_string(&log_path).unwrap();
+        let newest = std::fs::read_to_string(&list(&dir, &log_path).unwrap()[0]).unwrap();
+
+        assert_eq!(restored, "version 1");
+        assert_eq!(newest, "oops");
+    }
+
+    #[test]
+    fn test_restore_without_backups() {
+        let log = TempLog::new("current");
+        let dir = log.dir.join("backups");
+        let backup = log.dir.join("old.csv");
+        std::fs::write(&backup, "old").unwrap();
+
+        create(&dir, &log.path, 0).unwrap();
+        assert!(list(&dir, &log.path).unwrap().is_empty());
+
+        restore(&dir, &backup, &log.path, 0).unwrap();
+        let backups = list(&dir, &log.path).unwrap();
+        assert_eq!(log.contents(), "old");
+        assert_eq!(backups.len(), 1);
+        assert_eq!(std::fs::read_to_string(&backups[0]).unwrap(), "current");
+    }
+}
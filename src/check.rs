@@ -0,0 +1,180 @@
+use std::fmt::Display;
+
+use chrono::{Duration, NaiveTime};
+
+use crate::{
+    config::Categories,
+    entries::{self, Entry, EntryRaw},
+    error::line_of,
+};
+
+/// A problem found in the log
+#[derive(Debug, PartialEq, Eq)]
+pub struct Problem {
+    /// Line number in the log, starting at 1
+    pub line: u64,
+    pub message: String,
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.line, self.message)
+    }
+}
+
+/// Lint the log, in file order.
+///
+/// Reports malformed rows, duplicate or out of order timestamps, tags with stray
+/// whitespace, entries longer than `max_duration`, and days that don't start with an
+/// entry in an off task category.
+pub fn check(
+    log_contents: &str,
+    virtual_midnight: NaiveTime,
+    max_duration: Duration,
+    categories: &Categories,
+) -> Vec<Problem> {
+    let mut reader = entries::csv_reader(log_contents.as_bytes());
+
+    let line_at = |position: Option<&csv::Position>| line_of(log_contents, position);
+
+    let mut problems = vec![];
+    let mut previous: Option<EntryRaw> = None;
+
+    for record in reader.records() {
+        let record = match record {
+            Ok(r) => r,
+            Err(e) => {
+                let line = line_at(e.position());
+                problems.push(Problem {
+                    line,
+                    message: format!("Malformed row: {}", e),
+                });
+                continue;
+            }
+        };
+        let line = line_at(record.position());
+        let mut report = |message: String| problems.push(Problem { line, message });
+
+        if record.len() > 5 {
+            report(format!(
+                "Malformed row: {} fields, expected at most 5 (unquoted comma?)",
+                record.len()
+            ));
+            continue;
+        }
+        let entry: EntryRaw = match record.deserialize(None) {
+            Ok(e) => e,
+            Err(e) => {
+                report(format!("Malformed row: {}", e));
+                continue;
+            }
+        };
+
+        if let Some(tags) = record.get(3) {
+            for (i, tag) in tags.split(',').enumerate() {
+                // Tags are written separated by ", "
+                let tag = if i > 0 {
+                    tag.strip_prefix(' ').unwrap_or(tag)
+                } else {
+                    tag
+                };
+                let stray = match tag.split_once('=') {
+                    Some((key, value)) => key.trim() != key || value.trim() != value,
+                    None => tag.trim() != tag,
+                };
+                if stray {
+                    report(format!("Tag \"{}\" has stray whitespace", tag));
+                }
+            }
+        }
+
+        let date = entry.effective_date(virtual_midnight);
+        match &previous {
+            Some(prev) if prev.effective_date(virtual_midnight) == date => {
+                if entry.end == prev.end {
+                    report(format!("Duplicate timestamp {}", entry.end));
+                } else if entry.end < prev.end {
+                    report(format!(
+                        "Out of order: {} is before the previous entry at {}",
+                        entry.end, prev.end
+                    ));
+                } else if entry.end - prev.end > max_duration {
+                    let duration = entry.end - prev.end;
+                    report(format!(
+                        "Entry lasts {}h {}m. Forgot to log something?",
+                        duration.num_hours(),
+                        duration.num_minutes() % 60
+                    ));
+                }
+            }
+            _ => {
+                if previous.as_ref().map_or(false, |prev| entry.end < prev.end) {
+                    report(format!(
+                        "Out of order: {} is before the previous entry",
+                        entry.end
+                    ));
+                }
+                if categories.is_on_task(&Entry::from_raw(&entry)) {
+                    report(format!(
+                        "Day {} doesn't start with an off task entry (e.g. **arrive)",
+                        date
+                    ));
+                }
+            }
+        }
+
+        previous = Some(entry);
+    }
+
+    problems
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{Duration, NaiveTime};
+
+    use super::check;
+    use crate::config::Categories;
+
+    fn lint(log_contents: &str) -> Vec<(u64, String)> {
+        check(
+            log_contents,
+            NaiveTime::from_hms_opt(2, 0, 0).unwrap(),
+            Duration::hours(12),
+            &Categories::default(),
+        )
+        .into_iter()
+        .map(|p| (p.line, p.message))
+        .collect()
+    }
+
+    #[test]
+    fn test_check_good_file() {
+        let log_contents = std::fs::read_to_string("./test/test.csv").unwrap();
+        assert!(lint(&log_contents).is_empty());
+    }
+
+    #[test]
+    fn test_check_problems() {
+        let problems = lint(
+            "2023-06-14 09:00, , **arrive, \n\
+             2023-06-14 09:00, PROJ, dup, \n\
+             2023-06-14 08:00, PROJ, early, \n\
+             2023-06-14 23:00, PROJ, long,\"a , b\"\n\
+             2023-06-14 nope, PROJ, bad date, \n\
+             2023-06-14 23:30, PROJ, a, b, c, d\n\
+             2023-06-14 23:45, PROJ, kv, client =acme\n\
+             \n\
+             2023-06-15 09:00, PROJ, no arrive, \n",
+        );
+        let lines: Vec<u64> = problems.iter().map(|p| p.0).collect();
+
+        assert_eq!(lines, vec![2, 3, 4, 4, 5, 6, 7, 9]);
+        assert!(problems[0].1.starts_with("Duplicate"));
+        assert!(problems[1].1.starts_with("Out of order"));
+        assert!(problems[2].1.contains("stray whitespace"));
+        assert!(problems[3].1.starts_with("Entry lasts 15h 0m"));
+        assert!(problems[6].1.contains("stray whitespace"));
+        assert!(problems[7].1.contains("doesn't start with an off task"));
+    }
+}
@@ -1,28 +1,195 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, str::FromStr};
 
 use chrono::NaiveTime;
 use serde::{Deserialize, Serialize};
 
-use crate::files;
+use crate::{entries::Entry, error::AppError, files, keys::Keys, theme::Theme};
 
-pub const DEFAULT_STR: &str = r#"virtual_midnight = 2"#;
+pub const DEFAULT_STR: &str = r#"virtual_midnight = 2
+backups = 5"#;
 
 #[derive(Deserialize, Serialize, Default)]
 pub struct Config {
     #[serde(with = "naive_time")]
     pub virtual_midnight: NaiveTime,
+    /// Number of log backups to keep. 0 disables backups
+    #[serde(default = "default_backups")]
+    pub backups: usize,
+    /// Activity of the off task entries that fill time before a range or duration entry
+    #[serde(default = "default_filler")]
+    pub filler: String,
+    #[serde(default)]
+    pub ledger: LedgerConfig,
+    #[serde(default)]
+    pub categories: Categories,
+    #[serde(default)]
+    pub keys: Keys,
+    #[serde(default)]
+    pub theme: Theme,
 }
 
-pub fn read_config(path: String) -> Config {
+fn default_backups() -> usize {
+    5
+}
+
+fn default_filler() -> String {
+    String::from("**untracked")
+}
+
+impl FromStr for Config {
+    type Err = toml::de::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut config: Config = toml::from_str(s)?;
+        config.categories.filler = config.filler.clone();
+        Ok(config)
+    }
+}
+
+/// Settings for `export ledger`
+#[derive(Deserialize, Serialize)]
+#[serde(default)]
+pub struct LedgerConfig {
+    /// Account used for on task entries without a project
+    pub default_account: String,
+    /// Account used for off task entries. Off task entries are skipped if unset
+    pub off_task_account: Option<String>,
+}
+
+impl Default for LedgerConfig {
+    fn default() -> Self {
+        LedgerConfig {
+            default_account: String::from("unassigned"),
+            off_task_account: None,
+        }
+    }
+}
+
+/// A named bucket of logged time with its own total in the summary
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct Category {
+    pub name: String,
+    /// Matches entries whose activity starts or ends with this, e.g. `**`
+    #[serde(default)]
+    pub marker: Option<String>,
+    /// Matches entries in any of these projects
+    #[serde(default)]
+    pub projects: Vec<String>,
+    /// Matches entries with any of these tags, as `key` or `key=value`
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Whether time in this category counts as on task
+    #[serde(default = "default_on_task")]
+    pub on_task: bool,
+}
+
+fn default_on_task() -> bool {
+    true
+}
+
+impl Category {
+    /// A category with no marker, projects or tags catches entries no other category matches
+    pub fn is_catch_all(&self) -> bool {
+        self.marker.is_none() && self.projects.is_empty() && self.tags.is_empty()
+    }
+
+    pub fn matches(&self, entry: &Entry) -> bool {
+        let activity = entry.activity.trim();
+        self.marker.as_deref().map_or(false, |marker| {
+            !marker.is_empty() && (activity.starts_with(marker) || activity.ends_with(marker))
+        }) || self.projects.iter().any(|p| p == entry.project.trim())
+            || entry
+                .tags
+                .iter()
+                .any(|tag| self.tags.iter().any(|t| tag.matches(t)))
+    }
+}
+
+/// Categories in the order they're shown and matched
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct Categories {
+    pub list: Vec<Category>,
+    /// The config's `filler` activity. Filler entries are always off task
+    #[serde(skip, default = "default_filler")]
+    pub filler: String,
+}
+
+impl Default for Categories {
+    fn default() -> Self {
+        let list = vec![
+            Category {
+                name: String::from("On task"),
+                marker: None,
+                projects: vec![],
+                tags: vec![],
+                on_task: true,
+            },
+            Category {
+                name: String::from("Other"),
+                marker: Some(String::from("**")),
+                projects: vec![],
+                tags: vec![],
+                on_task: false,
+            },
+        ];
+        Categories {
+            list,
+            filler: default_filler(),
+        }
+    }
+}
+
+impl Categories {
+    /// Index of the first category matching the entry, or of the first catch all category.
+    ///
+    /// Filler entries go in the first off task category
+    pub fn index_of(&self, entry: &Entry) -> Option<usize> {
+        if self.is_filler(entry) {
+            return self.list.iter().position(|c| !c.on_task);
+        }
+        self.list
+            .iter()
+            .position(|c| !c.is_catch_all() && c.matches(entry))
+            .or_else(|| self.list.iter().position(|c| c.is_catch_all()))
+    }
+
+    /// The first category matching the entry, or the first catch all category
+    pub fn of(&self, entry: &Entry) -> Option<&Category> {
+        self.index_of(entry).map(|i| &self.list[i])
+    }
+
+    /// Entries that don't match any category count as on task, except fillers
+    pub fn is_on_task(&self, entry: &Entry) -> bool {
+        !self.is_filler(entry) && self.of(entry).map_or(true, |c| c.on_task)
+    }
+
+    fn is_filler(&self, entry: &Entry) -> bool {
+        entry.project.trim().is_empty() && entry.activity.trim() == self.filler.trim()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Category> {
+        self.list.iter()
+    }
+}
+
+pub fn read_config(path: String) -> Result<Config, AppError> {
     let config_path = if path.is_empty() {
         files::config_path()
     } else {
         PathBuf::from(path)
     };
 
-    let res =
-        toml::from_str::<Config>(std::fs::read_to_string(config_path).unwrap().as_str()).unwrap();
-    res
+    let contents = std::fs::read_to_string(&config_path).map_err(|cause| AppError::Io {
+        file: config_path.clone(),
+        cause,
+    })?;
+    contents
+        .parse::<Config>()
+        .map_err(|cause| AppError::Config {
+            file: config_path,
+            cause,
+        })
 }
 
 mod naive_time {
@@ -42,18 +209,109 @@ mod naive_time {
 
 #[cfg(test)]
 mod test {
-    use chrono::NaiveTime;
+    use chrono::{Duration, NaiveDateTime, NaiveTime};
 
-    use super::read_config;
+    use super::{read_config, Config};
+    use crate::entries::{Entry, EntryGroup, EntryRaw};
     use std::str::FromStr;
 
     #[test]
     fn test_read_good_file() {
-        let config = read_config(String::from_str("./test/config.toml").unwrap());
+        let config = read_config(String::from_str("./test/config.toml").unwrap()).unwrap();
 
         assert_eq!(
             config.virtual_midnight,
             NaiveTime::from_hms_opt(2, 0, 0).unwrap()
         );
     }
+
+    #[test]
+    fn test_read_bad_file() {
+        assert!(read_config(String::from("./non-existant-file-for-testing-lipu-tenpo")).is_err());
+    }
+
+    #[test]
+    fn test_categories() {
+        let config: Config = toml::from_str(
+            r#"
+            virtual_midnight = 2
+
+            [[categories]]
+            name = "Work"
+
+            [[categories]]
+            name = "Meeting"
+            tags = ["meeting"]
+
+            [[categories]]
+            name = "Break"
+            marker = "**"
+            on_task = false
+
+            [[categories]]
+            name = "Commute"
+            projects = ["TRAVEL"]
+            on_task = false
+            "#,
+        )
+        .unwrap();
+        let category = |input: &str| {
+            let raw = EntryRaw::from_string(String::from(input), NaiveDateTime::default()).unwrap();
+            let entry = Entry::from_raw(&raw);
+            config.categories.of(&entry).map(|c| c.name.clone())
+        };
+
+        assert_eq!(category("PROJ: work").as_deref(), Some("Work"));
+        assert_eq!(
+            category("PROJ: standup +meeting").as_deref(),
+            Some("Meeting")
+        );
+        assert_eq!(category("**lunch").as_deref(), Some("Break"));
+        assert_eq!(category("lunch**").as_deref(), Some("Break"));
+        assert_eq!(category("TRAVEL: train").as_deref(), Some("Commute"));
+        // Only leading or trailing markers count
+        assert_eq!(category("fix a**b bug").as_deref(), Some("Work"));
+    }
+
+    #[test]
+    fn test_filler_and_identical_categories() {
+        let config: Config = r#"
+            virtual_midnight = 2
+            filler = "break"
+
+            [[categories]]
+            name = "Work"
+
+            [[categories]]
+            name = "Work"
+
+            [[categories]]
+            name = "Away"
+            marker = "**"
+            on_task = false
+            "#
+        .parse()
+        .unwrap();
+        let raw: Vec<EntryRaw> = ["09:00 **arrive", "10:00 PROJ: work", "11:00 break"]
+            .iter()
+            .map(|input| EntryRaw::from_string(String::from(*input), NaiveDateTime::default()))
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let group = EntryGroup::from_raw(&raw);
+        let totals: Vec<(&str, Duration)> = group
+            .totals(&config.categories)
+            .into_iter()
+            .map(|(c, total)| (c.name.as_str(), total))
+            .collect();
+
+        assert!(!config.categories.is_on_task(&group.entries[2]));
+        assert_eq!(
+            totals,
+            vec![
+                ("Work", Duration::hours(1)),
+                ("Work", Duration::zero()),
+                ("Away", Duration::hours(1))
+            ]
+        );
+    }
 }
@@ -1,16 +1,41 @@
 use std::{
+    collections::BTreeMap,
     error::Error,
     fmt::Display,
-    ops::{Add, Sub},
-    path::PathBuf,
+    fs::{File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    ops::{Add, Bound},
+    path::{Path, PathBuf},
 };
 
-use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime, Timelike};
-use csv::ByteRecord;
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime};
 use ratatui::prelude::*;
 use serde::{Deserialize, Serialize};
 
-use crate::app::App;
+use crate::{
+    app::App,
+    backup,
+    config::{Categories, Category},
+    error::AppError,
+    lock::{self, LogLock},
+    parse::{self, ParseError},
+};
+
+/// A `+tag`, or a `+key=value` tag
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Tag {
+    pub key: String,
+    pub value: Option<String>,
+}
+
+impl Tag {
+    /// Whether the tag matches a `key` or `key=value` filter
+    pub fn matches(&self, filter: &str) -> bool {
+        filter.parse::<Tag>().map_or(false, |filter| {
+            self.key == filter.key && (filter.value.is_none() || self.value == filter.value)
+        })
+    }
+}
 
 #[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
 pub struct EntryRaw {
@@ -18,8 +43,11 @@ pub struct EntryRaw {
     pub end: NaiveDateTime,
     pub project: String,
     pub activity: String,
-    #[serde(with = "string_vector")]
-    pub tags: Vec<String>,
+    #[serde(with = "tag_vector")]
+    pub tags: Vec<Tag>,
+    /// Optional fifth column. Older four column logs read as empty notes
+    #[serde(default)]
+    pub notes: String,
 }
 
 impl From<&Entry> for EntryRaw {
@@ -29,6 +57,7 @@ impl From<&Entry> for EntryRaw {
             project: value.project.clone(),
             activity: value.activity.clone(),
             tags: value.tags.clone(),
+            notes: value.notes.clone(),
         }
     }
 }
@@ -40,13 +69,14 @@ impl From<Entry> for EntryRaw {
             project: value.project,
             activity: value.activity,
             tags: value.tags,
+            notes: value.notes,
         }
     }
 }
 
 impl PartialOrd for EntryRaw {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.end.cmp(&other.end))
+        Some(self.cmp(other))
     }
 }
 
@@ -57,53 +87,37 @@ impl Ord for EntryRaw {
 }
 
 impl EntryRaw {
-    pub fn from_string(value: String, fallback_datetime: NaiveDateTime) -> EntryRaw {
-        let (dt, rest) = split_time_and_entry(value, fallback_datetime);
-        let datetime = dt.unwrap_or(fallback_datetime);
-
-        let (first, tags) = rest.split_once('+').unwrap_or((rest.as_str(), ""));
-        let (project, activity) = first.split_once(':').unwrap_or(("", first));
+    /// Parse an entry, ending at `fallback_datetime` if it doesn't give a time.
+    ///
+    /// See [`parse::parse_entry`], which also returns the start of ranges
+    pub fn from_string(
+        value: String,
+        fallback_datetime: NaiveDateTime,
+    ) -> Result<EntryRaw, ParseError> {
+        parse::parse_entry(&value, fallback_datetime).map(|parsed| parsed.entry)
+    }
 
+    /// Entry ending at `end`, filling time nothing else was logged for
+    pub fn filler(end: NaiveDateTime, activity: &str) -> EntryRaw {
         EntryRaw {
-            end: datetime,
-            project: String::from(project),
+            end,
             activity: String::from(activity),
-            tags: tags
-                .split('+')
-                .map(|f| -> String { String::from(f) })
-                .collect(),
+            ..Default::default()
         }
     }
 
-    fn effective_date(&self, virtual_midnight: NaiveTime) -> NaiveDate {
-        match self.end.time() < virtual_midnight {
-            true => self.end.date().pred_opt().unwrap(),
-            false => self.end.date(),
-        }
+    /// The day this entry belongs to, counting entries before virtual midnight as the previous day
+    pub fn effective_date(&self, virtual_midnight: NaiveTime) -> NaiveDate {
+        effective_date(self.end, virtual_midnight)
     }
 }
 
-pub fn split_time_and_entry(
-    value: String,
-    datetime: NaiveDateTime,
-) -> (Option<NaiveDateTime>, String) {
-    let date = datetime.date();
-
-    if let Some((time, rest)) = value.split_once(' ') {
-        if let Ok(time) = NaiveTime::parse_from_str(time, "%H:%M") {
-            return (Some(NaiveDateTime::new(date, time)), rest.to_string());
-        } else if let Some(time) = time.strip_prefix('-') {
-            if let Ok(time) = NaiveTime::parse_from_str(time, "%H:%M") {
-                let neg_dur = Duration::minutes(time.minute() as i64);
-                return (Some(datetime.sub(neg_dur)), rest.to_string());
-            } else if let Ok(time) = time.parse::<i64>() {
-                let neg_dir = Duration::minutes(time);
-                return (Some(datetime.sub(neg_dir)), rest.to_string());
-            }
-        }
+/// The day `datetime` belongs to, counting times before virtual midnight as the previous day
+pub fn effective_date(datetime: NaiveDateTime, virtual_midnight: NaiveTime) -> NaiveDate {
+    match datetime.time() < virtual_midnight {
+        true => datetime.date().pred_opt().unwrap(),
+        false => datetime.date(),
     }
-
-    (None, value)
 }
 
 #[derive(PartialEq, Eq, Hash, Clone)]
@@ -121,6 +135,16 @@ impl From<&Entry> for EntryTitle {
     }
 }
 
+impl From<&EntryRaw> for EntryTitle {
+    fn from(value: &EntryRaw) -> Self {
+        if value.project.is_empty() {
+            Self(value.activity.clone())
+        } else {
+            Self(format!("{}: {}", value.project, value.activity))
+        }
+    }
+}
+
 impl From<&EntryTitle> for String {
     fn from(value: &EntryTitle) -> Self {
         value.0.clone()
@@ -160,69 +184,33 @@ pub struct Entry {
     /// The activity performed.
     /// Can't be an empty string
     pub activity: String,
-    pub tags: Vec<String>,
+    pub tags: Vec<Tag>,
+    /// Free text notes about the activity.
+    /// Can be an empty string
+    pub notes: String,
 }
 
 impl From<&Entry> for Line<'_> {
     fn from(val: &Entry) -> Self {
-        if val.is_on_task() {
-            Line::styled(format!("{}", val), Style::new())
-        } else {
-            Line::styled(
-                format!("{}", val),
-                Style::default().add_modifier(Modifier::DIM),
-            )
-        }
+        Line::raw(format!("{}", val))
     }
 }
 
 impl From<&Entry> for Text<'_> {
     fn from(val: &Entry) -> Self {
-        if val.is_on_task() {
-            Text::raw(format!("{}", val))
-        } else {
-            Text::styled(
-                format!("{}", val),
-                Style::default().add_modifier(Modifier::DIM),
-            )
-        }
+        Text::raw(format!("{}", val))
     }
 }
 
 impl From<&Entry> for String {
     fn from(value: &Entry) -> String {
-        let duration = value.duration();
-        let duration_display = if duration.is_zero() {
-            String::new()
-        } else {
-            format!("{}h {}m", duration.num_hours(), duration.num_minutes() % 60)
-        };
-
-        format!(
-            "{:<8} {:<6} {}",
-            duration_display,
-            value.end.format("%H:%M"),
-            value.display_sans_time()
-        )
+        parse::format_line(value)
     }
 }
 
 impl Display for Entry {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let duration = self.duration();
-        let duration_display = if duration.is_zero() {
-            String::new()
-        } else {
-            format!("{}h {}m", duration.num_hours(), duration.num_minutes() % 60)
-        };
-
-        write!(
-            f,
-            "{:<8} {:<6} {}",
-            duration_display,
-            self.end.format("%H:%M"),
-            self.display_sans_time()
-        )
+        write!(f, "{}", parse::format_line(self))
     }
 }
 
@@ -234,6 +222,7 @@ impl Entry {
             project: raw.project.clone(),
             activity: raw.activity.clone(),
             tags: raw.tags.clone(),
+            notes: raw.notes.clone(),
         }
     }
 
@@ -247,6 +236,7 @@ impl Entry {
             project: raw.project.clone(),
             activity: raw.activity.clone(),
             tags: raw.tags.clone(),
+            notes: raw.notes.clone(),
         }
     }
 
@@ -260,16 +250,21 @@ impl Entry {
         self
     }
 
-    pub fn add_tag(mut self, tag: String) -> Entry {
+    pub fn add_tag(mut self, tag: Tag) -> Entry {
         self.tags.push(tag);
         self
     }
 
-    pub fn add_tags(mut self, tags: &mut Vec<String>) -> Entry {
+    pub fn add_tags(mut self, tags: &mut Vec<Tag>) -> Entry {
         self.tags.append(tags);
         self
     }
 
+    pub fn with_notes(mut self, notes: String) -> Entry {
+        self.notes = notes;
+        self
+    }
+
     pub fn with_start(mut self, datetime: NaiveDateTime) -> Entry {
         self.start = datetime;
         self
@@ -287,6 +282,7 @@ impl Entry {
             activity: String::new(),
             project: String::new(),
             tags: Vec::new(),
+            notes: String::new(),
         }
     }
 
@@ -294,10 +290,6 @@ impl Entry {
         self.end - self.start
     }
 
-    pub fn is_on_task(&self) -> bool {
-        !self.activity.contains("**")
-    }
-
     pub fn display_sans_time(&self) -> String {
         if self.project.is_empty() {
             self.activity.to_string()
@@ -307,41 +299,78 @@ impl Entry {
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct EntryGroup {
     pub entries: Vec<Entry>,
-    time_on_task: Duration,
-    time_off_task: Duration,
-}
-
-impl Default for EntryGroup {
-    fn default() -> Self {
-        EntryGroup {
-            entries: vec![],
-            time_on_task: Duration::seconds(0),
-            time_off_task: Duration::seconds(0),
-        }
-    }
 }
 
 impl EntryGroup {
-    pub fn new(entries: Vec<Entry>) -> EntryGroup {
-        let mut on_task = Duration::seconds(0);
-        let mut off_task = Duration::seconds(0);
-
-        for entry in &entries {
-            if entry.is_on_task() {
-                on_task = on_task.add(entry.duration());
+    /// Build a group from sorted raw entries, each starting when the previous one ended
+    pub fn from_raw(raw_entries: &[EntryRaw]) -> EntryGroup {
+        let mut entries = Vec::with_capacity(raw_entries.len());
+        for i in 0..raw_entries.len() {
+            if i == 0 {
+                entries.push(Entry::from_raw(&raw_entries[i]));
             } else {
-                off_task = off_task.add(entry.duration());
+                entries.push(Entry::from_raw_previous(
+                    &raw_entries[i],
+                    &raw_entries[i - 1],
+                ));
             }
         }
 
-        EntryGroup {
-            entries,
-            time_on_task: on_task,
-            time_off_task: off_task,
+        EntryGroup::new(entries)
+    }
+
+    pub fn new(entries: Vec<Entry>) -> EntryGroup {
+        EntryGroup { entries }
+    }
+
+    /// Total time in each category, in the categories' order
+    pub fn totals<'a>(&self, categories: &'a Categories) -> Vec<(&'a Category, Duration)> {
+        categories
+            .iter()
+            .enumerate()
+            .map(|(i, category)| {
+                let total = self
+                    .entries
+                    .iter()
+                    .filter(|e| categories.index_of(e) == Some(i))
+                    .fold(Duration::zero(), |total, e| total.add(e.duration()));
+                (category, total)
+            })
+            .collect()
+    }
+
+    /// Entries with a tag matching a `key` or `key=value` filter
+    pub fn with_tag(&self, filter: &str) -> EntryGroup {
+        EntryGroup::new(
+            self.entries
+                .iter()
+                .filter(|e| e.tags.iter().any(|t| t.matches(filter)))
+                .cloned()
+                .collect(),
+        )
+    }
+
+    /// Total on task time for each value of the `key` tag. Entries without a value for it are
+    /// under `None`
+    pub fn totals_by_tag(
+        &self,
+        key: &str,
+        categories: &Categories,
+    ) -> BTreeMap<Option<String>, Duration> {
+        let mut totals = BTreeMap::new();
+        for entry in self.entries.iter().filter(|e| categories.is_on_task(e)) {
+            let value = entry
+                .tags
+                .iter()
+                .find(|t| t.key == key && t.value.is_some())
+                .and_then(|t| t.value.clone());
+            let total = totals.entry(value).or_insert_with(Duration::zero);
+            *total = total.add(entry.duration());
         }
+        totals
     }
 
     pub fn len(&self) -> usize {
@@ -352,25 +381,10 @@ impl EntryGroup {
         self.entries.len() == 0
     }
 
-    pub fn time_on_task_display(&self) -> String {
-        format!(
-            "{}h {}m",
-            self.time_on_task.num_hours(),
-            self.time_on_task.num_minutes() % 60
-        )
-    }
-
-    pub fn time_off_task_display(&self) -> String {
-        format!(
-            "{}h {}m",
-            self.time_off_task.num_hours(),
-            self.time_off_task.num_minutes() % 60
-        )
-    }
-
-    pub fn time_since_last_display(&self) -> Option<String> {
+    /// Time from the last entry to `now`, if the last entry is earlier
+    pub fn time_since_last_display(&self, now: NaiveDateTime) -> Option<String> {
         if let Some(entry) = self.entries.last() {
-            let diff = chrono::Local::now().naive_local().time() - entry.end.time();
+            let diff = now - entry.end;
             if diff > Duration::zero() {
                 return Some(format!(
                     "{}h {}m",
@@ -384,139 +398,304 @@ impl EntryGroup {
     }
 }
 
+/// Parse every row of the log, in file order
+fn read_raw(log_contents: &str) -> Result<Vec<EntryRaw>, AppError> {
+    let mut reader = csv_reader(log_contents.as_bytes());
+
+    reader
+        .deserialize()
+        .map(|result| result.map_err(|e| AppError::parse(log_contents, e)))
+        .collect()
+}
+
 pub fn read_all_date(
-    log_contents: &String,
+    log_contents: &str,
     date: NaiveDate,
     virtual_midnight: NaiveTime,
-) -> Result<EntryGroup, Box<dyn Error>> {
-    let mut reader = csv::ReaderBuilder::new()
-        .has_headers(false)
-        .flexible(true)
-        .quoting(true)
-        .trim(csv::Trim::All)
-        .from_reader(log_contents.as_bytes());
+) -> Result<EntryGroup, AppError> {
+    let mut raw_entries = read_raw(log_contents)?;
+    raw_entries.retain(|e| e.effective_date(virtual_midnight) == date);
 
-    let read_results: Result<Vec<EntryRaw>, csv::Error> = reader
-        .deserialize()
-        .filter(|f: &Result<EntryRaw, csv::Error>| match f {
-            Ok(ent) => ent.effective_date(virtual_midnight) == date,
-            Err(_) => false,
-        })
-        .collect();
+    Ok(EntryGroup::from_raw(&raw_entries))
+}
 
-    let raw_entries = match read_results {
-        Ok(x) => x,
-        Err(error) => panic!("Read error {:?}", error),
-    };
+pub fn read_all(path: &PathBuf) -> Result<EntryGroup, AppError> {
+    let log_contents = std::fs::read_to_string(path).map_err(|cause| AppError::Io {
+        file: path.clone(),
+        cause,
+    })?;
 
-    let count = raw_entries.len();
-    let mut entries = vec![Entry::default(); count];
+    read_all_from_string(&log_contents).map_err(|e| e.in_file(path))
+}
 
-    for i in 0..count {
-        if i == 0 {
-            entries[i] = Entry::from_raw(&raw_entries[i]);
-        } else {
-            entries[i] = Entry::from_raw_previous(&raw_entries[i], &raw_entries[i - 1]);
-        }
-    }
+pub fn read_all_from_string(log_contents: &str) -> Result<EntryGroup, AppError> {
+    let mut raw_entries = read_raw(log_contents)?;
+    raw_entries.sort();
 
-    Ok(EntryGroup::new(entries))
+    Ok(EntryGroup::from_raw(&raw_entries))
 }
 
-pub fn read_all(path: &PathBuf) -> Result<EntryGroup, Box<dyn Error>> {
-    let mut reader = csv::ReaderBuilder::new()
-        .has_headers(false)
-        .flexible(true)
-        .quoting(true)
-        .trim(csv::Trim::All)
-        .from_path(path)?;
+/// Read every entry in the log, grouped by effective date.
+///
+/// Like [`read_all_date`], the first entry of each day has no duration.
+pub fn read_all_by_date(
+    log_contents: &str,
+    virtual_midnight: NaiveTime,
+) -> Result<BTreeMap<NaiveDate, EntryGroup>, AppError> {
+    Ok(LogIndex::from_log(log_contents, virtual_midnight)?.groups())
+}
 
-    let read_results: Result<Vec<EntryRaw>, csv::Error> = reader.deserialize().collect();
+/// Raw entries of the log grouped by effective date.
+///
+/// Built by parsing the log once and then kept up to date with [`LogIndex::insert`]
+/// so looking up a day doesn't need to read the log again.
+#[derive(Default)]
+pub struct LogIndex {
+    days: BTreeMap<NaiveDate, Vec<EntryRaw>>,
+    virtual_midnight: NaiveTime,
+}
 
-    let mut raw_entries = match read_results {
-        Ok(x) => x,
-        Err(error) => panic!("Read error {:?}", error),
-    };
+impl LogIndex {
+    pub fn from_log(log_contents: &str, virtual_midnight: NaiveTime) -> Result<LogIndex, AppError> {
+        let mut raw_entries = read_raw(log_contents)?;
+        raw_entries.sort();
 
-    raw_entries.sort();
+        let mut index = LogIndex {
+            days: BTreeMap::new(),
+            virtual_midnight,
+        };
+        for raw in raw_entries {
+            index
+                .days
+                .entry(raw.effective_date(virtual_midnight))
+                .or_default()
+                .push(raw);
+        }
 
-    let count = raw_entries.len();
-    let mut entries = vec![Entry::default(); count];
+        Ok(index)
+    }
 
-    for i in 0..count {
-        if i == 0 {
-            entries[i] = Entry::from_raw(&raw_entries[i]);
-        } else {
-            entries[i] = Entry::from_raw_previous(&raw_entries[i], &raw_entries[i - 1]);
-        }
+    /// Add an entry, keeping its day sorted
+    pub fn insert(&mut self, entry: EntryRaw) {
+        let day = self
+            .days
+            .entry(entry.effective_date(self.virtual_midnight))
+            .or_default();
+        let position = day.partition_point(|e| e <= &entry);
+        day.insert(position, entry);
     }
 
-    Ok(EntryGroup::new(entries))
-}
+    /// Entries for a single effective date
+    pub fn day(&self, date: NaiveDate) -> EntryGroup {
+        self.days
+            .get(&date)
+            .map(|raw| EntryGroup::from_raw(raw))
+            .unwrap_or_default()
+    }
 
-pub fn read_all_from_string(log_contents: &String) -> Result<EntryGroup, Box<dyn Error>> {
-    let mut reader = csv::ReaderBuilder::new()
-        .has_headers(false)
-        .flexible(true)
-        .quoting(true)
-        .trim(csv::Trim::All)
-        .from_reader(log_contents.as_bytes());
+    /// Whether any entries belong to an effective date
+    pub fn has_day(&self, date: NaiveDate) -> bool {
+        self.days.get(&date).map_or(false, |raw| !raw.is_empty())
+    }
 
-    let read_results: Result<Vec<EntryRaw>, csv::Error> = reader.deserialize().collect();
+    /// The closest effective date before `date` with entries
+    pub fn prev_day(&self, date: NaiveDate) -> Option<NaiveDate> {
+        self.days
+            .range(..date)
+            .rev()
+            .find(|(_, raw)| !raw.is_empty())
+            .map(|(date, _)| *date)
+    }
 
-    let mut raw_entries = match read_results {
-        Ok(x) => x,
-        Err(error) => panic!("Read error {:?}", error),
-    };
+    /// The closest effective date after `date` with entries
+    pub fn next_day(&self, date: NaiveDate) -> Option<NaiveDate> {
+        self.days
+            .range((Bound::Excluded(date), Bound::Unbounded))
+            .find(|(_, raw)| !raw.is_empty())
+            .map(|(date, _)| *date)
+    }
 
-    raw_entries.sort();
+    /// Entries for every effective date in the log
+    pub fn groups(&self) -> BTreeMap<NaiveDate, EntryGroup> {
+        self.days
+            .iter()
+            .map(|(date, raw)| (*date, EntryGroup::from_raw(raw)))
+            .collect()
+    }
 
-    let count = raw_entries.len();
-    let mut entries = vec![Entry::default(); count];
+    /// Every entry in the log, oldest first
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &EntryRaw> {
+        self.days.values().flatten()
+    }
 
-    for i in 0..count {
-        if i == 0 {
-            entries[i] = Entry::from_raw(&raw_entries[i]);
-        } else {
-            entries[i] = Entry::from_raw_previous(&raw_entries[i], &raw_entries[i - 1]);
-        }
+    /// The last entry on the same effective date as `datetime` that ends at or before it
+    pub fn last_before(&self, datetime: NaiveDateTime) -> Option<&EntryRaw> {
+        let date = effective_date(datetime, self.virtual_midnight);
+        self.days
+            .get(&date)
+            .and_then(|day| day.iter().rev().find(|e| e.end <= datetime))
     }
 
-    Ok(EntryGroup::new(entries))
+    /// Entries that overlap the time between `start` and `end`
+    pub fn overlapping(&self, start: NaiveDateTime, end: NaiveDateTime) -> Vec<Entry> {
+        let first = effective_date(start, self.virtual_midnight);
+        let last = effective_date(end, self.virtual_midnight);
+        self.days
+            .range(first..=last)
+            .flat_map(|(_, raw)| EntryGroup::from_raw(raw).entries)
+            .filter(|e| e.start < end && e.end > start)
+            .collect()
+    }
+
+    pub fn latest(&self) -> Option<&EntryRaw> {
+        self.days.values().next_back().and_then(|day| day.last())
+    }
 }
 
-pub fn write(app: &App, entry: EntryRaw) -> Result<(), Box<dyn Error>> {
+/// Add entries to the log in a single write.
+///
+/// If they're all after the latest entry they're appended to the end of the log,
+/// otherwise the log is backed up and rewritten in order.
+pub fn write(app: &App, entries: &[EntryRaw]) -> Result<(), Box<dyn Error>> {
+    // Held until the log is written so no other write can interleave
+    let _lock = LogLock::exclusive(&app.log_path())?;
+    write_locked(app, entries)
+}
+
+/// [`write`] for callers already holding the log's exclusive [`LogLock`]
+pub fn write_locked(app: &App, entries: &[EntryRaw]) -> Result<(), Box<dyn Error>> {
     let log_path = app.log_path();
-    let mut path_string = log_path.clone().into_os_string();
-    path_string.push("-tmp");
-    let temp_path: PathBuf = path_string.into();
+    let virtual_midnight = app.config.virtual_midnight;
+    let mut entries = entries.to_vec();
+    entries.sort();
+    let Some(first) = entries.first() else {
+        return Ok(());
+    };
+
+    if std::fs::metadata(&log_path)?.len() == 0 {
+        return append_to(&log_path, None, &entries, virtual_midnight);
+    }
+    if let Some(last) = read_last(&log_path)? {
+        if *first >= last {
+            return append_to(&log_path, Some(&last), &entries, virtual_midnight);
+        }
+    }
+
+    backup::create(&backup::dir(&log_path), &log_path, app.config.backups)?;
+    let temp_path = lock::temp_path(&log_path);
     let mut entries_raw: Vec<EntryRaw> = read_all(&log_path)?
         .entries
         .iter()
         .map(EntryRaw::from)
         .collect();
-    entries_raw.push(entry);
+    entries_raw.append(&mut entries);
     entries_raw.sort();
-    write_to(
-        &log_path,
-        &temp_path,
-        &entries_raw,
-        app.config.virtual_midnight,
-    )?;
+    write_to(&log_path, &temp_path, &entries_raw, virtual_midnight)?;
+    Ok(())
+}
+
+/// How much of the end of the log to read when looking for the last entry
+const TAIL_BYTES: u64 = 4096;
+
+/// Read the last entry of the log without parsing the rest of it.
+///
+/// Returns None if the last line can't be found or parsed.
+fn read_last(path: &Path) -> Result<Option<EntryRaw>, std::io::Error> {
+    let mut file = File::open(path)?;
+    let offset = file.metadata()?.len().saturating_sub(TAIL_BYTES);
+    file.seek(SeekFrom::Start(offset))?;
+    let mut tail = Vec::new();
+    file.read_to_end(&mut tail)?;
+
+    // Skip the (likely partial) line the tail starts in
+    let tail = if offset > 0 {
+        match tail.iter().position(|b| *b == b'\n') {
+            Some(i) => &tail[i + 1..],
+            None => return Ok(None),
+        }
+    } else {
+        &tail[..]
+    };
+
+    let Some(line) = tail
+        .split(|b| *b == b'\n')
+        .rev()
+        .find(|line| !line.iter().all(u8::is_ascii_whitespace))
+    else {
+        return Ok(None);
+    };
+
+    let mut reader = csv_reader(line);
+
+    Ok(reader.deserialize().next().and_then(Result::ok))
+}
+
+/// Append sorted entries that all come after `previous`, the last entry in the log
+fn append_to(
+    path: &Path,
+    previous: Option<&EntryRaw>,
+    entries: &[EntryRaw],
+    virtual_midnight: NaiveTime,
+) -> Result<(), Box<dyn Error>> {
+    let mut file = OpenOptions::new().read(true).append(true).open(path)?;
+
+    // Written with a single write so a failure can't leave only some of the entries
+    let mut out = Vec::new();
+    let len = file.metadata()?.len();
+    if len > 0 {
+        let mut last_byte = [0];
+        file.seek(SeekFrom::End(-1))?;
+        file.read_exact(&mut last_byte)?;
+        if last_byte[0] != b'\n' {
+            out.push(b'\n');
+        }
+    }
+
+    let mut previous = previous;
+    for entry in entries {
+        // Same layout as write_to
+        if previous.map_or(false, |p| {
+            p.effective_date(virtual_midnight) != entry.effective_date(virtual_midnight)
+        }) {
+            out.extend_from_slice(b"\n\n");
+        }
+        let mut writer = csv_writer(&mut out);
+        writer.serialize(entry)?;
+        writer.flush()?;
+        drop(writer);
+        previous = Some(entry);
+    }
+
+    file.write_all(&out)?;
     Ok(())
 }
 
+/// Reader for rows in the log's layout
+pub fn csv_reader<R: std::io::Read>(reader: R) -> csv::Reader<R> {
+    csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .quoting(true)
+        .trim(csv::Trim::All)
+        .from_reader(reader)
+}
+
+fn csv_writer<W: std::io::Write>(writer: W) -> csv::Writer<W> {
+    csv::WriterBuilder::new()
+        .has_headers(false)
+        .quote_style(csv::QuoteStyle::Necessary)
+        .flexible(true)
+        .from_writer(writer)
+}
+
 pub fn write_to(
     path: &PathBuf,
     temp_path: &PathBuf,
     entries: &[EntryRaw],
     virtual_midnight: NaiveTime,
 ) -> Result<(), std::io::Error> {
-    let mut writer = csv::WriterBuilder::new()
-        .has_headers(false)
-        .quote_style(csv::QuoteStyle::Never)
-        .flexible(true)
-        .from_path(temp_path)?;
+    let file = File::create(temp_path)?;
+    let mut writer = csv_writer(&file);
 
     for i in 0..entries.len() {
         // If this Entry is effectively the start of the day, add some line breaks before
@@ -524,11 +703,12 @@ pub fn write_to(
             && entries[i].effective_date(virtual_midnight)
                 != entries[i - 1].effective_date(virtual_midnight)
         {
-            writer.write_field("\n")?;
-            writer.write_byte_record(&ByteRecord::new())?;
+            writer.flush()?;
+            (&file).write_all(b"\n\n")?;
         }
         writer.serialize(&entries[i])?;
     }
+    writer.flush()?;
 
     // for entry in entries {
     //     if entry.end.time().hour() > 2 {
@@ -545,50 +725,55 @@ mod naive_date_time {
     use serde::{de::Error, Deserialize, Deserializer, Serializer};
 
     pub fn serialize<S: Serializer>(dt: &NaiveDateTime, serializer: S) -> Result<S::Ok, S::Error> {
-        serializer.serialize_str(dt.format("%Y-%m-%d %H:%M").to_string().as_str())
+        serializer.serialize_str(&parse::format_datetime(dt))
     }
 
     pub fn deserialize<'de, D: Deserializer<'de>>(
         deserializer: D,
     ) -> Result<NaiveDateTime, D::Error> {
-        let dt = Deserialize::deserialize(deserializer)?;
-        Ok(NaiveDateTime::parse_from_str(dt, "%Y-%m-%d %H:%M")
-            .map_err(D::Error::custom)
-            .unwrap())
+        let dt: &str = Deserialize::deserialize(deserializer)?;
+        parse::parse_datetime(dt).map_err(D::Error::custom)
     }
 }
 
-mod string_vector {
+mod tag_vector {
+    use super::{parse, Tag};
     use serde::{Deserialize, Deserializer, Serializer};
 
-    pub fn serialize<S>(vector: &[String], serializer: S) -> Result<S::Ok, S::Error>
+    pub fn serialize<S>(tags: &[Tag], serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        serializer.serialize_str(&vector.join(", "))
+        serializer.serialize_str(&parse::format_tags(tags))
     }
 
-    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<Tag>, D::Error>
     where
         D: Deserializer<'de>,
     {
         let s = String::deserialize(deserializer)?;
-
-        if s.is_empty() {
-            Ok(Vec::<String>::new())
-        } else {
-            Ok(s.split(',').map(ToOwned::to_owned).collect::<Vec<String>>())
-        }
+        Ok(parse::parse_tags(&s))
     }
 }
 
 #[cfg(test)]
 mod test {
+    use chrono::Duration;
     use chrono::NaiveDate;
     use chrono::NaiveTime;
 
     use super::read_all;
     use super::read_all_date;
+    use super::read_all_from_string;
+    use super::write;
+    use super::write_to;
+    use super::EntryGroup;
+    use super::EntryRaw;
+    use super::Tag;
+    use crate::config::Categories;
+    use crate::error::AppError;
+    use crate::parse;
+    use crate::testing::TempLog;
     use std::path::PathBuf;
 
     #[test]
@@ -611,7 +796,7 @@ mod test {
 
     #[test]
     fn test_date_read_good_file() {
-        let log_contents = std::fs::read_to_string(&PathBuf::from("./test/test.csv")).unwrap();
+        let log_contents = std::fs::read_to_string(PathBuf::from("./test/test.csv")).unwrap();
         let result = read_all_date(
             &log_contents,
             NaiveDate::from_ymd_opt(2023, 6, 14).unwrap(),
@@ -626,4 +811,173 @@ mod test {
         assert_eq!(entries.len(), 5);
         assert_eq!(entries[0].activity, "**arrive");
     }
+
+    #[test]
+    fn test_category_totals() {
+        let log_contents = std::fs::read_to_string("./test/test.csv").unwrap();
+        let group = read_all_date(
+            &log_contents,
+            NaiveDate::from_ymd_opt(2023, 6, 14).unwrap(),
+            NaiveTime::from_hms_opt(2, 0, 0).unwrap(),
+        )
+        .unwrap();
+        let categories = Categories::default();
+        let totals: Vec<(&str, String)> = group
+            .totals(&categories)
+            .into_iter()
+            .map(|(c, total)| (c.name.as_str(), parse::format_duration(total)))
+            .collect();
+
+        assert_eq!(
+            totals,
+            vec![
+                ("On task", String::from("7h 15m")),
+                ("Other", String::from("1h 0m"))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_key_value_tags() {
+        let datetime = NaiveDate::from_ymd_opt(2023, 6, 14)
+            .unwrap()
+            .and_hms_opt(9, 0, 0)
+            .unwrap();
+        let raw: Vec<EntryRaw> = [
+            "09:00 **arrive",
+            "10:00 PROJ: work +client=acme +urgent",
+            "11:00 PROJ: more work +client = globex",
+            "11:30 PROJ: admin",
+            "12:00 PROJ: call +client=acme",
+            "12:30 **lunch",
+            "13:00 PROJ: email +client=",
+        ]
+        .iter()
+        .map(|input| EntryRaw::from_string(String::from(*input), datetime).unwrap())
+        .collect();
+        let group = EntryGroup::from_raw(&raw);
+
+        assert_eq!(
+            raw[1].tags,
+            vec![
+                Tag {
+                    key: String::from("client"),
+                    value: Some(String::from("acme"))
+                },
+                "urgent".parse::<Tag>().unwrap()
+            ]
+        );
+        assert_eq!(raw[2].tags[0].to_string(), "client=globex");
+        assert_eq!(group.with_tag("client").len(), 4);
+        assert_eq!(group.with_tag("client=acme").len(), 2);
+        assert_eq!(group.with_tag("urgent").len(), 1);
+
+        let totals = group.totals_by_tag("client", &Categories::default());
+        assert_eq!(totals[&Some(String::from("acme"))], Duration::minutes(90));
+        assert_eq!(totals[&Some(String::from("globex"))], Duration::hours(1));
+        // Off task time and empty values aren't counted as a value
+        assert_eq!(raw[6].tags[0].value, None);
+        assert_eq!(totals[&None], Duration::hours(1));
+    }
+
+    #[test]
+    fn test_from_string_notes() {
+        let datetime = NaiveDate::from_ymd_opt(2023, 6, 14)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap();
+        let entry = EntryRaw::from_string(
+            String::from("PROJ: work +tag -- fixed the +1 bug"),
+            datetime,
+        )
+        .unwrap();
+
+        assert_eq!(entry.project, "PROJ");
+        assert_eq!(entry.tags, vec!["tag".parse::<Tag>().unwrap()]);
+        assert_eq!(entry.notes, "fixed the +1 bug");
+    }
+
+    #[test]
+    fn test_notes_round_trip() {
+        let log = TempLog::new("");
+        let log_contents = String::from(
+            "2023-06-14 09:00, , **arrive, \n\
+             2023-06-14 12:00, PROJ, work,\"a, b\",\"see notes, for details\"\n",
+        );
+
+        let entries: Vec<EntryRaw> = read_all_from_string(&log_contents)
+            .unwrap()
+            .entries
+            .iter()
+            .map(EntryRaw::from)
+            .collect();
+        assert_eq!(entries[0].notes, "");
+
+        write_to(
+            &log.path,
+            &log.dir.join("timelog.csv-tmp"),
+            &entries,
+            NaiveTime::from_hms_opt(2, 0, 0).unwrap(),
+        )
+        .unwrap();
+        let written = read_all(&log.path).unwrap().entries;
+
+        assert_eq!(
+            written[1].tags,
+            vec!["a".parse::<Tag>().unwrap(), "b".parse::<Tag>().unwrap()]
+        );
+        assert_eq!(written[1].notes, "see notes, for details");
+    }
+
+    #[test]
+    fn test_write_appends_latest_and_rewrites_earlier() {
+        let original = std::fs::read_to_string("./test/test.csv").unwrap();
+        let log = TempLog::new(&original);
+        let app = log.app();
+        let datetime = |d, h| {
+            NaiveDate::from_ymd_opt(2023, 6, d)
+                .unwrap()
+                .and_hms_opt(h, 0, 0)
+                .unwrap()
+        };
+
+        write(
+            &app,
+            &[EntryRaw::from_string(String::from("**arrive"), datetime(16, 9)).unwrap()],
+        )
+        .unwrap();
+        let appended = log.contents();
+
+        write(
+            &app,
+            &[EntryRaw::from_string(String::from("**early"), datetime(14, 8)).unwrap()],
+        )
+        .unwrap();
+        let rewritten = read_all(&log.path).unwrap().entries;
+
+        assert_eq!(
+            appended,
+            format!(
+                "{}\n\n\n2023-06-16 09:00,,**arrive,,\n",
+                original.trim_end_matches('\n')
+            )
+        );
+        assert_eq!(rewritten.len(), 11);
+        assert_eq!(rewritten[0].activity, "**early");
+        assert_eq!(rewritten[10].activity, "**arrive");
+    }
+
+    #[test]
+    fn test_read_malformed_row() {
+        let log =
+            TempLog::new("2023-06-14 09:00, , **arrive, \n\n\n2023-06-14 nine, PROJ, work, \n");
+
+        match read_all(&log.path) {
+            Err(AppError::Parse { file, line, .. }) => {
+                assert_eq!(file.as_ref(), Some(&log.path));
+                assert_eq!(line, 4);
+            }
+            _ => panic!("Expected a parse error"),
+        }
+    }
 }
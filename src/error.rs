@@ -0,0 +1,101 @@
+use std::{fmt::Display, io, path::PathBuf};
+
+/// Errors reading or writing the log & config
+#[derive(Debug)]
+pub enum AppError {
+    /// A row of the log couldn't be parsed
+    Parse {
+        /// None when parsing log contents that didn't come straight from a file
+        file: Option<PathBuf>,
+        /// Line number, starting at 1
+        line: u64,
+        cause: csv::Error,
+    },
+    /// A file couldn't be read or written
+    Io { file: PathBuf, cause: io::Error },
+    /// The config file isn't valid
+    Config {
+        file: PathBuf,
+        cause: toml::de::Error,
+    },
+}
+
+impl AppError {
+    /// Build a [`AppError::Parse`] from a csv error, finding its line in `contents`
+    pub fn parse(contents: &str, cause: csv::Error) -> AppError {
+        AppError::Parse {
+            file: None,
+            line: line_of(contents, cause.position()),
+            cause,
+        }
+    }
+
+    /// Set the file a [`AppError::Parse`] came from
+    pub fn in_file(self, path: &std::path::Path) -> AppError {
+        match self {
+            AppError::Parse { line, cause, .. } => AppError::Parse {
+                file: Some(path.to_path_buf()),
+                line,
+                cause,
+            },
+            e => e,
+        }
+    }
+}
+
+impl Display for AppError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AppError::Parse {
+                file: Some(file),
+                line,
+                cause,
+            } => write!(f, "{}:{}: {}", file.display(), line, cause_message(cause)),
+            AppError::Parse {
+                file: None,
+                line,
+                cause,
+            } => write!(f, "line {}: {}", line, cause_message(cause)),
+            AppError::Io { file, cause } => write!(f, "{}: {}", file.display(), cause),
+            AppError::Config { file, cause } => write!(f, "{}: {}", file.display(), cause),
+        }
+    }
+}
+
+impl std::error::Error for AppError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AppError::Parse { cause, .. } => Some(cause),
+            AppError::Io { cause, .. } => Some(cause),
+            AppError::Config { cause, .. } => Some(cause),
+        }
+    }
+}
+
+/// The csv error without its own (blank line ignoring) position
+fn cause_message(cause: &csv::Error) -> String {
+    match cause.kind() {
+        csv::ErrorKind::Deserialize { err, .. } => err.to_string(),
+        _ => cause.to_string(),
+    }
+}
+
+/// Line number of a csv position in `contents`.
+///
+/// csv doesn't count blank lines and positions records before any it skipped,
+/// so this works from the byte offset instead.
+pub fn line_of(contents: &str, position: Option<&csv::Position>) -> u64 {
+    let bytes = contents.as_bytes();
+    position.map_or(0, |p| {
+        let start = (p.byte() as usize).min(bytes.len());
+        let blank = bytes[start..]
+            .iter()
+            .take_while(|b| **b == b'\n' || **b == b'\r')
+            .count();
+        bytes[..start + blank]
+            .iter()
+            .filter(|b| **b == b'\n')
+            .count() as u64
+            + 1
+    })
+}
@@ -0,0 +1,206 @@
+use std::collections::BTreeMap;
+
+use chrono::{Duration, NaiveDate};
+
+use crate::{
+    config::{Categories, LedgerConfig},
+    entries::{Entry, EntryGroup},
+};
+
+/// Format entries as hledger/ledger timeclock `i` and `o` lines.
+///
+/// The account is the project (or [`LedgerConfig::default_account`]) followed by
+/// any plain tags as subaccounts, and `key=value` tags become ledger `key:value` tags.
+/// Entries in off task categories use [`LedgerConfig::off_task_account`] or are
+/// skipped if it's unset.
+pub fn ledger(
+    days: &BTreeMap<NaiveDate, EntryGroup>,
+    config: &LedgerConfig,
+    categories: &Categories,
+) -> String {
+    let mut out = String::new();
+
+    for entry in days.values().flat_map(|group| group.entries.iter()) {
+        if entry.duration().is_zero() {
+            continue;
+        }
+
+        let account = if categories.is_on_task(entry) {
+            ledger_account(entry, &config.default_account)
+        } else if let Some(account) = &config.off_task_account {
+            account.clone()
+        } else {
+            continue;
+        };
+
+        out.push_str(&format!(
+            "i {} {}  {}",
+            entry.start.format("%Y/%m/%d %H:%M:%S"),
+            account,
+            entry.activity.trim()
+        ));
+        let comment = ledger_comment(entry);
+        if !comment.is_empty() {
+            out.push_str(&format!("  ; {}", comment));
+        }
+        out.push('\n');
+        out.push_str(&format!("o {}\n\n", entry.end.format("%Y/%m/%d %H:%M:%S")));
+    }
+
+    out
+}
+
+/// Format entries as org-mode headings with `CLOCK` lines.
+///
+/// Each effective day gets a top level heading with one subheading per
+/// project/activity, in the order they were first logged that day.
+pub fn org(days: &BTreeMap<NaiveDate, EntryGroup>) -> String {
+    let mut out = String::new();
+
+    for (date, group) in days {
+        let mut headings: Vec<(String, Vec<&Entry>)> = vec![];
+        for entry in group.entries.iter().filter(|e| !e.duration().is_zero()) {
+            let title = entry.display_sans_time().trim().to_string();
+            match headings.iter_mut().find(|(t, _)| *t == title) {
+                Some((_, clocks)) => clocks.push(entry),
+                None => headings.push((title, vec![entry])),
+            }
+        }
+
+        if headings.is_empty() {
+            continue;
+        }
+
+        out.push_str(&format!("* {}\n", date.format("%Y-%m-%d %a")));
+        for (title, clocks) in headings {
+            out.push_str(&format!("** {}\n", title));
+            for entry in &clocks {
+                out.push_str(&format!(
+                    "CLOCK: [{}]--[{}] => {}\n",
+                    entry.start.format("%Y-%m-%d %a %H:%M"),
+                    entry.end.format("%Y-%m-%d %a %H:%M"),
+                    org_duration(entry.duration())
+                ));
+            }
+            for entry in clocks.iter().filter(|e| !e.notes.is_empty()) {
+                out.push_str(&format!("- {}\n", entry.notes));
+            }
+        }
+    }
+
+    out
+}
+
+fn org_duration(duration: Duration) -> String {
+    format!(
+        "{:>2}:{:02}",
+        duration.num_hours(),
+        duration.num_minutes() % 60
+    )
+}
+
+fn ledger_account(entry: &Entry, default_account: &str) -> String {
+    let project = entry.project.trim();
+    let mut parts = vec![if project.is_empty() {
+        default_account
+    } else {
+        project
+    }];
+    parts.extend(
+        entry
+            .tags
+            .iter()
+            .filter(|t| t.value.is_none() && !t.key.is_empty())
+            .map(|t| t.key.as_str()),
+    );
+    parts.join(":")
+}
+
+/// Notes followed by `key=value` tags as ledger `key:value` tags
+fn ledger_comment(entry: &Entry) -> String {
+    let mut parts = vec![];
+    if !entry.notes.is_empty() {
+        parts.push(entry.notes.clone());
+    }
+    parts.extend(
+        entry
+            .tags
+            .iter()
+            .filter_map(|t| t.value.as_ref().map(|value| format!("{}:{}", t.key, value))),
+    );
+    parts.join(", ")
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::NaiveTime;
+
+    use super::{ledger, org};
+    use crate::{
+        config::{Categories, LedgerConfig},
+        entries::read_all_by_date,
+    };
+
+    fn test_days() -> std::collections::BTreeMap<chrono::NaiveDate, crate::entries::EntryGroup> {
+        let log_contents = std::fs::read_to_string("./test/test.csv").unwrap();
+        read_all_by_date(&log_contents, NaiveTime::from_hms_opt(2, 0, 0).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_ledger_skips_off_task() {
+        let out = ledger(
+            &test_days(),
+            &LedgerConfig::default(),
+            &Categories::default(),
+        );
+
+        assert!(out
+            .starts_with("i 2023/06/14 09:00:00 unassigned  dev meeting\no 2023/06/14 09:30:00\n"));
+        assert!(out.contains("i 2023/06/14 09:30:00 PROJ  work\no 2023/06/14 12:00:00\n"));
+        assert!(!out.contains("lunch"));
+        // First entry of a day never spans the night before
+        assert!(!out.contains("i 2023/06/14 17:15:00"));
+    }
+
+    #[test]
+    fn test_ledger_off_task_account() {
+        let config = LedgerConfig {
+            off_task_account: Some(String::from("break")),
+            ..Default::default()
+        };
+        let out = ledger(&test_days(), &config, &Categories::default());
+
+        assert!(out.contains("i 2023/06/14 12:00:00 break  **lunch\no 2023/06/14 13:00:00\n"));
+    }
+
+    #[test]
+    fn test_ledger_key_value_tags() {
+        let days = read_all_by_date(
+            "2023-06-14 09:00, , **arrive, \n\
+             2023-06-14 10:00, PROJ, work,\"client=acme, bug\",fixed it\n",
+            NaiveTime::from_hms_opt(2, 0, 0).unwrap(),
+        )
+        .unwrap();
+        let out = ledger(&days, &LedgerConfig::default(), &Categories::default());
+
+        assert_eq!(
+            out,
+            "i 2023/06/14 09:00:00 PROJ:bug  work  ; fixed it, client:acme\n\
+             o 2023/06/14 10:00:00\n\n"
+        );
+    }
+
+    #[test]
+    fn test_org_groups_by_day_and_title() {
+        let out = org(&test_days());
+
+        assert!(out.starts_with("* 2023-06-14 Wed\n** dev meeting\n"));
+        assert!(out.contains(
+            "** PROJ: work\n\
+             CLOCK: [2023-06-14 Wed 09:30]--[2023-06-14 Wed 12:00] =>  2:30\n\
+             CLOCK: [2023-06-14 Wed 13:00]--[2023-06-14 Wed 17:15] =>  4:15\n"
+        ));
+        assert!(out.contains("* 2023-06-15 Thu\n"));
+        assert_eq!(out.matches("** PROJ: work").count(), 2);
+    }
+}
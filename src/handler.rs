@@ -1,5 +1,12 @@
-use crate::app::{App, AppResult, InputMode};
-use crossterm::event::{Event as CrosstermEvent, KeyCode, KeyEvent, KeyModifiers};
+use crate::{
+    app::{App, AppResult, InputMode},
+    keys::Action,
+};
+use crossterm::event::{
+    Event as CrosstermEvent, KeyCode, KeyEvent, MouseButton, MouseEvent, MouseEventKind,
+};
+use ratatui::layout::Position;
+use tui_input::backend::crossterm::EventHandler;
 
 pub fn handle_key_events(app: &mut App, key_evt: KeyEvent) -> AppResult<()> {
     match app.input_mode {
@@ -12,61 +19,47 @@ pub fn handle_key_events(app: &mut App, key_evt: KeyEvent) -> AppResult<()> {
             }
             _ => {}
         },
-        InputMode::Logging => match key_evt.modifiers {
-            KeyModifiers::CONTROL => match key_evt.code {
-                KeyCode::Left => match app.move_prev_day() {
-                    Ok(_) => {}
-                    Err(e) => {
-                        println!("{}", e)
-                    }
-                },
-                KeyCode::Right => match app.move_next_day() {
-                    Ok(_) => {}
-                    Err(e) => {
-                        println!("{}", e)
-                    }
-                },
-                KeyCode::Char('c') => app.quit(),
-                KeyCode::Char('q') => app.quit(),
-                KeyCode::Home => app.move_to_today(),
-                KeyCode::Char('h') => app.move_to_today(),
-                _ => {}
-            },
-            _ => match key_evt.code {
-                KeyCode::Enter => {
-                    app.commit_current_log();
-                    app.refresh();
-                }
-                KeyCode::Esc => {
-                    app.refresh();
-                }
-                KeyCode::Up => {
-                    app.search_back();
-                }
-                KeyCode::Down => {
-                    app.search_forward();
-                }
-                KeyCode::Right => {
-                    if app.search_cursor >= 0 && app.input.cursor() == app.input.value().len() {
-                        app.accept_history();
-                    } else {
-                        app.handle_event(&CrosstermEvent::Key(KeyEvent {
-                            code: key_evt.code,
-                            modifiers: key_evt.modifiers,
-                            kind: key_evt.kind,
-                            state: key_evt.state,
-                        }));
-                    }
+        // Bound actions come first, and the arrow keys always move around the calendar
+        InputMode::PickingDate => match (app.config.keys.action(&key_evt), key_evt.code) {
+            (Some(Action::Commit), _) => app.pick_date(app.picker_date),
+            (Some(Action::Cancel), _) => app.close_date_picker(),
+            (Some(Action::ScrollUp), _) => app.move_picker_months(-1),
+            (Some(Action::ScrollDown), _) => app.move_picker_months(1),
+            (_, KeyCode::Left) => app.move_picker(-1),
+            (_, KeyCode::Right) => app.move_picker(1),
+            (_, KeyCode::Up) => app.move_picker(-7),
+            (_, KeyCode::Down) => app.move_picker(7),
+            _ => {}
+        },
+        InputMode::GoingToDate => match app.config.keys.action(&key_evt) {
+            Some(Action::Commit) => {
+                if let Err(e) = app.submit_date_prompt() {
+                    app.status = Some(e.to_string());
                 }
-                KeyCode::Tab => {
+            }
+            Some(Action::Cancel) => app.close_date_prompt(),
+            _ => {
+                app.status = None;
+                app.date_input.handle_event(&CrosstermEvent::Key(key_evt));
+            }
+        },
+        InputMode::SearchingHistory => match app.config.keys.action(&key_evt) {
+            Some(Action::SearchBack) => app.move_history_cursor(-1),
+            Some(Action::SearchForward) => app.move_history_cursor(1),
+            Some(Action::ScrollUp) => app.move_history_cursor(-10),
+            Some(Action::ScrollDown) => app.move_history_cursor(10),
+            Some(Action::Commit | Action::AcceptHistory) => app.accept_history_match(),
+            Some(Action::Cancel) => app.close_history_search(),
+            _ => app.handle_history_event(&CrosstermEvent::Key(key_evt)),
+        },
+        InputMode::Logging => match app.config.keys.action(&key_evt) {
+            Some(action) => handle_action(app, action),
+            None => match key_evt.code {
+                KeyCode::Right
+                    if app.search_cursor >= 0 && app.input.cursor() == app.input.value().len() =>
+                {
                     app.accept_history();
                 }
-                KeyCode::PageUp => {
-                    app.scroll_log_up();
-                }
-                KeyCode::PageDown => {
-                    app.scroll_log_down();
-                }
                 KeyCode::Backspace => {
                     app.cancel_search();
                     app.handle_backspace_into_time();
@@ -91,3 +84,186 @@ pub fn handle_key_events(app: &mut App, key_evt: KeyEvent) -> AppResult<()> {
 
     Ok(())
 }
+
+pub fn handle_mouse_events(app: &mut App, mouse_evt: MouseEvent) -> AppResult<()> {
+    let position = Position::new(mouse_evt.column, mouse_evt.row);
+    match app.input_mode {
+        InputMode::Editing | InputMode::GoingToDate | InputMode::SearchingHistory => {}
+        InputMode::PickingDate => match mouse_evt.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                match app
+                    .areas
+                    .picker
+                    .iter()
+                    .find(|(_, rect)| rect.contains(position))
+                {
+                    Some((date, _)) => app.pick_date(*date),
+                    None => app.close_date_picker(),
+                }
+            }
+            MouseEventKind::ScrollUp => app.move_picker_months(-1),
+            MouseEventKind::ScrollDown => app.move_picker_months(1),
+            _ => {}
+        },
+        InputMode::Logging => match mouse_evt.kind {
+            MouseEventKind::ScrollUp if app.areas.log.contains(position) => app.scroll_log_up(),
+            MouseEventKind::ScrollDown if app.areas.log.contains(position) => app.scroll_log_down(),
+            MouseEventKind::Down(MouseButton::Left) => {
+                if app.areas.date.contains(position) {
+                    app.open_date_picker();
+                } else if app.areas.log.contains(position) {
+                    let row = (position.y - app.areas.log.y) as usize;
+                    app.select_entry(app.log_scroll + row);
+                } else if app.areas.suggestion.contains(position) {
+                    app.accept_history();
+                }
+            }
+            _ => {}
+        },
+    }
+
+    Ok(())
+}
+
+fn handle_action(app: &mut App, action: Action) {
+    match action {
+        Action::PrevDay => {
+            if let Err(e) = app.move_prev() {
+                app.status = Some(e.to_string());
+            }
+        }
+        Action::NextDay => {
+            if let Err(e) = app.move_next() {
+                app.status = Some(e.to_string());
+            }
+        }
+        Action::PrevLoggedDay => {
+            if let Err(e) = app.move_prev_logged_day() {
+                app.status = Some(e.to_string());
+            }
+        }
+        Action::NextLoggedDay => {
+            if let Err(e) = app.move_next_logged_day() {
+                app.status = Some(e.to_string());
+            }
+        }
+        Action::Today => app.move_to_today(),
+        Action::GoToDate => app.open_date_prompt(),
+        Action::SelectPrev => app.select_prev_entry(),
+        Action::SelectNext => app.select_next_entry(),
+        Action::SearchBack => app.search_back(),
+        Action::SearchForward => app.search_forward(),
+        Action::AcceptHistory => {
+            if !app.complete() {
+                app.accept_history();
+            }
+        }
+        Action::SearchHistory => app.open_history_search(),
+        Action::ScrollUp => app.scroll_log_up(),
+        Action::ScrollDown => app.scroll_log_down(),
+        Action::ToggleWeek => app.toggle_week_view(),
+        Action::Commit => match app.commit_current_log() {
+            Ok(()) => app.refresh(),
+            // Keep the input so the entry isn't lost
+            Err(e) => app.status = Some(e.to_string()),
+        },
+        Action::Cancel => app.refresh(),
+        Action::Quit => app.quit(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crossterm::event::{
+        KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+    };
+    use ratatui::{backend::TestBackend, Terminal};
+
+    use super::{handle_key_events, handle_mouse_events};
+    use crate::{
+        app::{App, InputMode},
+        config::Config,
+        ui,
+    };
+
+    fn click(app: &mut App, column: u16, row: u16) {
+        let mouse = MouseEvent {
+            kind: MouseEventKind::Down(MouseButton::Left),
+            column,
+            row,
+            modifiers: KeyModifiers::NONE,
+        };
+        handle_mouse_events(app, mouse).unwrap();
+    }
+
+    fn press(app: &mut App, code: KeyCode, modifiers: KeyModifiers) {
+        handle_key_events(app, KeyEvent::new(code, modifiers)).unwrap();
+    }
+
+    #[test]
+    fn test_rebound_keys_in_pickers() {
+        let mut app = App::new(
+            String::from("./test/test.csv"),
+            String::from("./test/config.toml"),
+        )
+        .unwrap();
+        let config: Config = toml::from_str(
+            r#"
+            virtual_midnight = 2
+            [keys]
+            cancel = "ctrl-x"
+            scroll_down = "alt-j"
+            "#,
+        )
+        .unwrap();
+        app.config.keys = config.keys;
+
+        app.open_history_search();
+        press(&mut app, KeyCode::Esc, KeyModifiers::NONE);
+        assert!(matches!(app.input_mode, InputMode::SearchingHistory));
+        press(&mut app, KeyCode::Char('x'), KeyModifiers::CONTROL);
+        assert!(matches!(app.input_mode, InputMode::Logging));
+
+        app.open_date_picker();
+        let date = app.picker_date;
+        press(&mut app, KeyCode::Char('j'), KeyModifiers::ALT);
+        press(&mut app, KeyCode::Left, KeyModifiers::NONE);
+        assert_eq!(
+            app.picker_date,
+            date.checked_add_months(chrono::Months::new(1)).unwrap() - chrono::Duration::days(1)
+        );
+        press(&mut app, KeyCode::Char('x'), KeyModifiers::CONTROL);
+        assert!(matches!(app.input_mode, InputMode::Logging));
+    }
+
+    #[test]
+    fn test_mouse_select_and_pick_date() {
+        let mut app = App::new(
+            String::from("./test/test.csv"),
+            String::from("./test/config.toml"),
+        )
+        .unwrap();
+        app.current_date = chrono::NaiveDate::from_ymd_opt(2023, 6, 14).unwrap();
+        app.refresh();
+        let mut terminal = Terminal::new(TestBackend::new(80, 24)).unwrap();
+        let mut draw = |app: &mut App| {
+            terminal.draw(|frame| ui::render(app, frame)).unwrap();
+        };
+        draw(&mut app);
+
+        let log = app.areas.log;
+        click(&mut app, log.x + 1, log.y + 1);
+        assert_eq!(app.selected_entry, Some(1));
+
+        let date = app.areas.date;
+        click(&mut app, date.x + 1, date.y);
+        assert!(matches!(app.input_mode, InputMode::PickingDate));
+
+        draw(&mut app);
+        let (day, rect) = app.areas.picker[19];
+        assert_eq!(day, chrono::NaiveDate::from_ymd_opt(2023, 6, 20).unwrap());
+        click(&mut app, rect.x, rect.y);
+        assert!(matches!(app.input_mode, InputMode::Logging));
+        assert_eq!(app.current_date, day);
+    }
+}
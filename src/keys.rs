@@ -0,0 +1,456 @@
+use std::{collections::BTreeMap, fmt::Display, str::FromStr};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{Deserialize, Serialize};
+
+use crate::app::View;
+
+/// Something a key chord can be bound to in the `[keys]` config table
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    PrevDay,
+    NextDay,
+    PrevLoggedDay,
+    NextLoggedDay,
+    Today,
+    GoToDate,
+    SelectPrev,
+    SelectNext,
+    SearchBack,
+    SearchForward,
+    AcceptHistory,
+    SearchHistory,
+    ScrollUp,
+    ScrollDown,
+    ToggleWeek,
+    Commit,
+    Cancel,
+    Quit,
+}
+
+impl Action {
+    /// Short description for the hotkeys line in `view`, if it's shown there
+    fn help(&self, view: &View) -> Option<&'static str> {
+        let week = matches!(view, View::Week);
+        match self {
+            Action::PrevDay if week => Some("Prev week"),
+            Action::NextDay if week => Some("Next week"),
+            Action::PrevDay => Some("Prev day"),
+            Action::NextDay => Some("Next day"),
+            Action::Today => Some("Today"),
+            Action::GoToDate => Some("Go to date"),
+            Action::SelectPrev => Some("Prev entry"),
+            Action::SelectNext => Some("Next entry"),
+            Action::SearchHistory => Some("History"),
+            Action::ToggleWeek if week => Some("Day"),
+            Action::ToggleWeek => Some("Week"),
+            _ => None,
+        }
+    }
+}
+
+/// A key with modifiers, written like `ctrl-left`, `shift-up`, `pagedown` or `h`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyChord {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyChord {
+    /// Whether the input uses the chord for typing or moving its cursor: a character,
+    /// backspace, delete, left, right, home or end without modifiers
+    fn edits_input(&self) -> bool {
+        self.modifiers.is_empty()
+            && matches!(
+                self.code,
+                KeyCode::Char(_)
+                    | KeyCode::Backspace
+                    | KeyCode::Delete
+                    | KeyCode::Left
+                    | KeyCode::Right
+                    | KeyCode::Home
+                    | KeyCode::End
+            )
+    }
+
+    pub fn matches(&self, event: &KeyEvent) -> bool {
+        let mut modifiers = event.modifiers;
+        // Shifted characters already differ by case
+        if let KeyCode::Char(_) = event.code {
+            modifiers.remove(KeyModifiers::SHIFT);
+        }
+        self.code == event.code && self.modifiers == modifiers
+    }
+}
+
+const NAMED_KEYS: [(&str, KeyCode); 14] = [
+    ("Left", KeyCode::Left),
+    ("Right", KeyCode::Right),
+    ("Up", KeyCode::Up),
+    ("Down", KeyCode::Down),
+    ("Home", KeyCode::Home),
+    ("End", KeyCode::End),
+    ("PageUp", KeyCode::PageUp),
+    ("PageDown", KeyCode::PageDown),
+    ("Tab", KeyCode::Tab),
+    ("Enter", KeyCode::Enter),
+    ("Esc", KeyCode::Esc),
+    ("Backspace", KeyCode::Backspace),
+    ("Delete", KeyCode::Delete),
+    ("Space", KeyCode::Char(' ')),
+];
+
+const MODIFIERS: [(&str, KeyModifiers); 3] = [
+    ("Ctrl", KeyModifiers::CONTROL),
+    ("Alt", KeyModifiers::ALT),
+    ("Shift", KeyModifiers::SHIFT),
+];
+
+impl FromStr for KeyChord {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut rest = s.trim();
+        let mut modifiers = KeyModifiers::NONE;
+        'strip: loop {
+            for (name, modifier) in MODIFIERS {
+                let prefix = rest.get(..name.len() + 1);
+                if prefix.map_or(false, |p| {
+                    (p.ends_with('-') || p.ends_with('+'))
+                        && p[..name.len()].eq_ignore_ascii_case(name)
+                }) && rest.len() > name.len() + 1
+                {
+                    modifiers |= modifier;
+                    rest = &rest[name.len() + 1..];
+                    continue 'strip;
+                }
+            }
+            break;
+        }
+
+        let code = if let Some((_, code)) = NAMED_KEYS
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(rest))
+        {
+            *code
+        } else if let Some(n) = rest
+            .strip_prefix(['f', 'F'])
+            .and_then(|n| n.parse::<u8>().ok())
+            .filter(|n| (1..=12).contains(n))
+        {
+            KeyCode::F(n)
+        } else {
+            let mut chars = rest.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => KeyCode::Char(c),
+                _ => return Err(format!("Unknown key \"{}\"", s)),
+            }
+        };
+
+        // Shifted characters arrive as their shifted form, which `matches` compares without SHIFT
+        if let KeyCode::Char(c) = code {
+            if modifiers.contains(KeyModifiers::SHIFT) && c != ' ' {
+                if !c.is_alphabetic() {
+                    return Err(format!(
+                        "Can't bind \"{}\", write the shifted character instead of shift",
+                        s
+                    ));
+                }
+                modifiers.remove(KeyModifiers::SHIFT);
+                return Ok(KeyChord {
+                    code: KeyCode::Char(c.to_uppercase().next().unwrap_or(c)),
+                    modifiers,
+                });
+            }
+        }
+        Ok(KeyChord { code, modifiers })
+    }
+}
+
+impl Display for KeyChord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (name, modifier) in MODIFIERS {
+            if self.modifiers.contains(modifier) {
+                write!(f, "{}-", name)?;
+            }
+        }
+        match NAMED_KEYS.iter().find(|(_, code)| *code == self.code) {
+            Some((name, _)) => write!(f, "{}", name),
+            None => match self.code {
+                KeyCode::F(n) => write!(f, "F{}", n),
+                KeyCode::Char(c) => write!(f, "{}", c),
+                code => write!(f, "{:?}", code),
+            },
+        }
+    }
+}
+
+/// One chord or a list of them
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Chords {
+    One(String),
+    Many(Vec<String>),
+}
+
+/// Key chords for each action. Actions missing from the config keep their defaults
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(
+    try_from = "BTreeMap<Action, Chords>",
+    into = "BTreeMap<Action, Vec<String>>"
+)]
+pub struct Keys(BTreeMap<Action, Vec<KeyChord>>);
+
+impl Default for Keys {
+    fn default() -> Self {
+        let bindings: [(Action, &[&str]); 18] = [
+            (Action::PrevDay, &["ctrl-left"]),
+            (Action::NextDay, &["ctrl-right"]),
+            (Action::PrevLoggedDay, &["alt-left"]),
+            (Action::NextLoggedDay, &["alt-right"]),
+            (Action::Today, &["ctrl-home", "ctrl-h"]),
+            (Action::GoToDate, &["ctrl-g"]),
+            (Action::SelectPrev, &["shift-up"]),
+            (Action::SelectNext, &["shift-down"]),
+            (Action::SearchBack, &["up"]),
+            (Action::SearchForward, &["down"]),
+            (Action::AcceptHistory, &["tab"]),
+            (Action::SearchHistory, &["ctrl-r"]),
+            (Action::ScrollUp, &["pageup"]),
+            (Action::ScrollDown, &["pagedown"]),
+            (Action::ToggleWeek, &["ctrl-w"]),
+            (Action::Commit, &["enter"]),
+            (Action::Cancel, &["esc"]),
+            (Action::Quit, &["ctrl-c", "ctrl-q"]),
+        ];
+        Keys(
+            bindings
+                .into_iter()
+                .map(|(action, chords)| {
+                    let chords = chords.iter().map(|c| c.parse().unwrap()).collect();
+                    (action, chords)
+                })
+                .collect(),
+        )
+    }
+}
+
+impl Keys {
+    /// The action bound to a key press
+    pub fn action(&self, event: &KeyEvent) -> Option<Action> {
+        self.0
+            .iter()
+            .find(|(_, chords)| chords.iter().any(|c| c.matches(event)))
+            .map(|(action, _)| *action)
+    }
+
+    /// Hotkeys line for `view` listing the first chord of the actions worth showing
+    pub fn help(&self, view: &View) -> String {
+        self.0
+            .iter()
+            .filter_map(|(action, chords)| Some((action.help(view)?, chords.first()?)))
+            .map(|(help, chord)| format!("{}: {}", chord, help))
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+
+    /// Every chord bound to the actions, like `Enter/Tab`
+    pub fn chords(&self, actions: &[Action]) -> String {
+        actions
+            .iter()
+            .filter_map(|action| self.0.get(action))
+            .flatten()
+            .map(|c| c.to_string())
+            .collect::<Vec<String>>()
+            .join("/")
+    }
+}
+
+impl TryFrom<BTreeMap<Action, Chords>> for Keys {
+    type Error = String;
+
+    fn try_from(config: BTreeMap<Action, Chords>) -> Result<Self, Self::Error> {
+        let mut keys = Keys::default();
+        for (action, chords) in config {
+            let chords = match chords {
+                Chords::One(chord) => vec![chord],
+                Chords::Many(chords) => chords,
+            };
+            let chords = chords
+                .iter()
+                .map(|c| c.parse())
+                .collect::<Result<Vec<KeyChord>, String>>()?;
+            if let Some(chord) = chords.iter().find(|c| c.edits_input()) {
+                return Err(format!(
+                    "{} can't be bound to {}, the input needs it for editing",
+                    chord,
+                    action_name(&action)
+                ));
+            }
+            keys.0.insert(action, chords);
+        }
+
+        let mut bound: Vec<(KeyChord, Action)> = vec![];
+        for (action, chords) in keys.0.iter() {
+            for chord in chords {
+                match bound.iter().find(|(c, _)| c == chord) {
+                    Some((_, other)) if other != action => {
+                        return Err(format!(
+                            "{} is bound to both {} and {}",
+                            chord,
+                            action_name(other),
+                            action_name(action)
+                        ))
+                    }
+                    Some(_) => {}
+                    None => bound.push((*chord, *action)),
+                }
+            }
+        }
+        Ok(keys)
+    }
+}
+
+impl From<Keys> for BTreeMap<Action, Vec<String>> {
+    fn from(keys: Keys) -> Self {
+        keys.0
+            .into_iter()
+            .map(|(action, chords)| (action, chords.iter().map(|c| c.to_string()).collect()))
+            .collect()
+    }
+}
+
+/// Name of an action as written in the config
+fn action_name(action: &Action) -> String {
+    toml::Value::try_from(action)
+        .ok()
+        .and_then(|v| v.as_str().map(String::from))
+        .unwrap_or_else(|| format!("{:?}", action))
+}
+
+#[cfg(test)]
+mod test {
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    use super::{Action, KeyChord, Keys};
+    use crate::app::View;
+    use crate::config::Config;
+
+    fn press(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    #[test]
+    fn test_parse_chords() {
+        let chord: KeyChord = "Ctrl+Shift-PageUp".parse().unwrap();
+        assert_eq!(chord.code, KeyCode::PageUp);
+        assert_eq!(chord.modifiers, KeyModifiers::CONTROL | KeyModifiers::SHIFT);
+        assert_eq!(chord.to_string(), "Ctrl-Shift-PageUp");
+        assert_eq!("alt--".parse::<KeyChord>().unwrap().to_string(), "Alt--");
+        assert_eq!("f5".parse::<KeyChord>().unwrap().code, KeyCode::F(5));
+        assert!("ctrl-nope".parse::<KeyChord>().is_err());
+
+        // Shifted letters are written uppercase, and shifted symbols can't be
+        let chord: KeyChord = "ctrl-shift-a".parse().unwrap();
+        assert_eq!(chord.to_string(), "Ctrl-A");
+        assert!(chord.matches(&press(
+            KeyCode::Char('A'),
+            KeyModifiers::CONTROL | KeyModifiers::SHIFT
+        )));
+        assert!("alt-shift-1".parse::<KeyChord>().is_err());
+    }
+
+    #[test]
+    fn test_config_keys() {
+        let config: Config = toml::from_str(
+            r#"
+            virtual_midnight = 2
+
+            [keys]
+            prev_day = "alt-p"
+            next_day = ["alt-n", "ctrl-right"]
+            "#,
+        )
+        .unwrap();
+        let keys = &config.keys;
+
+        assert_eq!(
+            keys.action(&press(KeyCode::Char('p'), KeyModifiers::ALT)),
+            Some(Action::PrevDay)
+        );
+        assert_eq!(
+            keys.action(&press(KeyCode::Left, KeyModifiers::CONTROL)),
+            None
+        );
+        assert_eq!(
+            keys.action(&press(KeyCode::Right, KeyModifiers::CONTROL)),
+            Some(Action::NextDay)
+        );
+        // Unset actions keep their defaults
+        assert_eq!(
+            keys.action(&press(KeyCode::Char('h'), KeyModifiers::CONTROL)),
+            Some(Action::Today)
+        );
+        assert!(keys
+            .help(&View::Day)
+            .starts_with("Alt-p: Prev day, Alt-n: Next day"));
+        assert_eq!(
+            Keys::default().help(&View::Day),
+            "Ctrl-Left: Prev day, Ctrl-Right: Next day, Ctrl-Home: Today, Ctrl-g: Go to date, \
+             Shift-Up: Prev entry, Shift-Down: Next entry, Ctrl-r: History, Ctrl-w: Week"
+        );
+        assert_eq!(
+            Keys::default().help(&View::Week),
+            "Ctrl-Left: Prev week, Ctrl-Right: Next week, Ctrl-Home: Today, Ctrl-g: Go to date, \
+             Shift-Up: Prev entry, Shift-Down: Next entry, Ctrl-r: History, Ctrl-w: Day"
+        );
+        assert_eq!(keys.chords(&[Action::NextDay]), "Alt-n/Ctrl-Right");
+        assert_eq!(
+            keys.chords(&[Action::Commit, Action::AcceptHistory]),
+            "Enter/Tab"
+        );
+    }
+
+    #[test]
+    fn test_config_key_conflicts() {
+        let error = toml::from_str::<Config>(
+            r#"
+            virtual_midnight = 2
+
+            [keys]
+            today = "ctrl-left"
+            "#,
+        )
+        .err()
+        .unwrap();
+        assert!(error
+            .to_string()
+            .contains("Ctrl-Left is bound to both prev_day and today"));
+
+        assert!(toml::from_str::<Config>(
+            r#"
+            virtual_midnight = 2
+            [keys]
+            jump = "j"
+            "#,
+        )
+        .is_err());
+
+        for chord in [
+            "j",
+            "shift-j",
+            "space",
+            "backspace",
+            "delete",
+            "left",
+            "right",
+            "home",
+            "end",
+        ] {
+            let config = format!("virtual_midnight = 2\n[keys]\nscroll_down = \"{}\"", chord);
+            let error = toml::from_str::<Config>(&config).err().unwrap();
+            assert!(error.to_string().contains("the input needs it for editing"));
+        }
+    }
+}
@@ -7,18 +7,49 @@ pub mod event;
 /// Widget renderer.
 pub mod ui;
 
+/// Colors and styles for the TUI
+pub mod theme;
+
 /// Terminal user interface.
 pub mod tui;
 
 /// Event handler.
 pub mod handler;
 
+/// Configurable key bindings
+pub mod keys;
+
 /// Utilities for constructing & manipulating log entries
 pub mod entries;
 
+/// Parsing and formatting entries
+pub mod parse;
+
+/// Matching and ranking history suggestions
+pub mod search;
+
 /// Utilities for getting log and config paths
 pub mod files;
 
+/// Advisory locking for the log file
+pub mod lock;
+
+/// Rotating backups of the log file
+pub mod backup;
+
+/// Log linter
+pub mod check;
+
+/// Exporters for other time tracking tools
+pub mod export;
+
 pub mod config;
 
+/// Errors reading the log & config
+pub mod error;
+
 pub mod subcommands;
+
+/// Fixtures shared by the unit tests
+#[cfg(test)]
+mod testing;
@@ -0,0 +1,98 @@
+use std::{
+    fs::{File, OpenOptions},
+    io,
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant},
+};
+
+use fs2::FileExt;
+
+/// How long to keep retrying before giving up on a lock
+const TIMEOUT: Duration = Duration::from_secs(5);
+const RETRY_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Advisory lock on a log file, released when dropped.
+///
+/// The lock is taken on a `.lock` file next to the log rather than the log
+/// itself, since writes replace the log with a renamed temp file.
+pub struct LogLock {
+    file: File,
+}
+
+impl LogLock {
+    /// Lock the log for reading. Other readers can hold the lock at the same time.
+    pub fn shared(log_path: &Path) -> io::Result<LogLock> {
+        LogLock::acquire(log_path, FileExt::try_lock_shared)
+    }
+
+    /// Lock the log for a read-modify-write. Waits for any readers and writers to finish.
+    pub fn exclusive(log_path: &Path) -> io::Result<LogLock> {
+        LogLock::acquire(log_path, FileExt::try_lock_exclusive)
+    }
+
+    fn acquire(log_path: &Path, try_lock: fn(&File) -> io::Result<()>) -> io::Result<LogLock> {
+        let path = lock_path(log_path);
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+
+        let start = Instant::now();
+        loop {
+            match try_lock(&file) {
+                Ok(()) => return Ok(LogLock { file }),
+                Err(e) if e.kind() == fs2::lock_contended_error().kind() => {
+                    if start.elapsed() >= TIMEOUT {
+                        return Err(io::Error::new(
+                            io::ErrorKind::WouldBlock,
+                            format!(
+                                "Timed out waiting for {}. Is another lipu-tenpo writing to the log?",
+                                path.display()
+                            ),
+                        ));
+                    }
+                    thread::sleep(RETRY_INTERVAL);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl Drop for LogLock {
+    fn drop(&mut self) {
+        let _ = FileExt::unlock(&self.file);
+    }
+}
+
+fn lock_path(log_path: &Path) -> PathBuf {
+    let mut path = log_path.as_os_str().to_owned();
+    path.push(".lock");
+    path.into()
+}
+
+/// A temp file path next to the log that's unique to this process
+pub fn temp_path(log_path: &Path) -> PathBuf {
+    let mut path = log_path.as_os_str().to_owned();
+    path.push(format!("-tmp-{}", std::process::id()));
+    path.into()
+}
+
+#[cfg(test)]
+mod test {
+    use super::LogLock;
+    use crate::testing::TempLog;
+
+    #[test]
+    fn test_shared_locks_coexist() {
+        let log = TempLog::new("");
+        let first = LogLock::shared(&log.path).unwrap();
+        let second = LogLock::shared(&log.path);
+
+        assert!(second.is_ok());
+        drop(first);
+    }
+}
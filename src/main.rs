@@ -3,6 +3,7 @@ use lipu_tenpo::app::App;
 /// This example is taken from https://raw.githubusercontent.com/fdehau/tui-rs/master/examples/user_input.rs
 use lipu_tenpo::event::{Event, EventHandler};
 use lipu_tenpo::handler;
+use lipu_tenpo::subcommands::ExportFormat;
 use lipu_tenpo::tui::Tui;
 use ratatui::{backend::CrosstermBackend, Terminal};
 use std::process::exit;
@@ -26,25 +27,78 @@ struct Cli {
 
 #[derive(Subcommand, Debug)]
 enum Commands {
-    Log { date: Option<Vec<String>> },
-    Add { entry: Option<Vec<String>> },
+    Log {
+        date: Option<Vec<String>>,
+        #[arg(
+            long,
+            value_name = "KEY[=VALUE]",
+            help = "Only show entries with this tag"
+        )]
+        tag: Option<String>,
+        #[arg(
+            long,
+            value_name = "KEY",
+            help = "Total time by the values of this tag"
+        )]
+        by: Option<String>,
+    },
+    Add {
+        entry: Option<Vec<String>>,
+    },
+    Export {
+        format: ExportFormat,
+    },
+    /// List log backups, or diff/restore one by its number in the list
+    Restore {
+        number: Option<usize>,
+        #[arg(
+            long,
+            requires = "number",
+            help = "Show changes from the backup to the log"
+        )]
+        diff: bool,
+    },
+    /// Check the log for problems. Exits with 1 if any are found
+    Check {
+        #[arg(long, default_value = "12", help = "Flag entries longer than this")]
+        max_hours: i64,
+    },
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("Error: {}", e);
+        exit(1);
+    }
+}
+
+fn run() -> Result<(), Box<dyn Error>> {
     let cli = Cli::parse();
 
     // Create the application
-    let mut app = App::new(cli.log.unwrap_or_default(), cli.config.unwrap_or_default());
+    let mut app = App::new(cli.log.unwrap_or_default(), cli.config.unwrap_or_default())?;
 
     match cli.command {
-        Some(Commands::Log { date }) => {
-            lipu_tenpo::subcommands::log(&app, date, cli.csv);
+        Some(Commands::Log { date, tag, by }) => {
+            lipu_tenpo::subcommands::log(&app, date, cli.csv, tag, by)?;
             exit(0);
         }
         Some(Commands::Add { entry }) => {
-            lipu_tenpo::subcommands::add(&app, entry);
+            lipu_tenpo::subcommands::add(&mut app, entry)?;
+            exit(0);
+        }
+        Some(Commands::Export { format }) => {
+            lipu_tenpo::subcommands::export(&app, format)?;
             exit(0);
         }
+        Some(Commands::Restore { number, diff }) => {
+            lipu_tenpo::subcommands::restore(&app, number, diff)?;
+            exit(0);
+        }
+        Some(Commands::Check { max_hours }) => {
+            let problems = lipu_tenpo::subcommands::check(&app, max_hours)?;
+            exit(if problems > 0 { 1 } else { 0 });
+        }
         None => {}
     }
 
@@ -59,17 +113,24 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     // Run the app
     while app.running {
-        // Render the UI
-        tui.draw(&mut app)?;
+        // Render the UI when something changed or the minute did
+        let now = chrono::Local::now().naive_local();
+        if app.needs_draw(now) {
+            tui.draw(&mut app)?;
+            app.drawn(now);
+        }
         // Handle events
         match tui.events.next()? {
-            // Event::Tick => app.tick(),
-            Event::Mouse(_) => {}
+            Event::Tick => app.tick(),
+            Event::Mouse(mouse) => {
+                app.redraw = true;
+                handler::handle_mouse_events(&mut app, mouse)?;
+            }
             Event::Key(key) => {
-                handler::handle_key_events(&mut app, key);
+                app.redraw = true;
+                handler::handle_key_events(&mut app, key)?;
             }
-            Event::Resize(_, _) => {}
-            _ => {}
+            Event::Resize(_, _) => app.redraw = true,
         }
     }
 
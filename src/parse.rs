@@ -0,0 +1,762 @@
+//! Parsing and formatting entries.
+//!
+//! Entries are typed as
+//!
+//! ```text
+//! [time] [project:] activity [+tag | +key=value ...] [-- notes]
+//! ```
+//!
+//! where the time is any form [`split_range_and_entry`] accepts. A tag starts at a `+`
+//! at the beginning of a word. `\` makes the punctuation or digit after it plain text,
+//! e.g. `\:` in a project or `\+1` in an activity.
+//!
+//! [`format_entry`] writes an entry in this form with its full date and time, and
+//! [`parse_entry`] gives the same entry back for any entry with a project, activity or tag.
+//!
+//! The log's time and tag columns use [`format_datetime`]/[`parse_datetime`] and
+//! [`format_tags`]/[`parse_tags`], and [`format_line`] is how entries are shown in the log.
+
+use std::{fmt::Display, str::FromStr};
+
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime, Timelike};
+
+use crate::entries::{Entry, EntryRaw, Tag};
+
+/// Format of the log's time column
+const LOG_DATETIME_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+/// An entry parsed from input
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedEntry {
+    pub entry: EntryRaw,
+    /// When the entry starts, if it was given as a range or duration
+    pub start: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// The entry has no project, activity or tags
+    Empty,
+    /// Something that looks like a time but isn't one, e.g. `25:00`
+    InvalidTime(String),
+    /// A tag without a key, or with a comma (which separates tags in the log)
+    InvalidTag(String),
+    /// A log time that isn't `YYYY-MM-DD HH:MM`
+    InvalidDateTime(String),
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::Empty => write!(f, "Nothing to log"),
+            ParseError::InvalidTime(time) => write!(f, "Invalid time \"{}\"", time),
+            ParseError::InvalidTag(tag) => write!(f, "Invalid tag \"{}\"", tag),
+            ParseError::InvalidDateTime(datetime) => {
+                write!(
+                    f,
+                    "Invalid time \"{}\", expected YYYY-MM-DD HH:MM",
+                    datetime
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Parse an entry, resolving times relative to `now`. Entries without a time end at `now`
+pub fn parse_entry(input: &str, now: NaiveDateTime) -> Result<ParsedEntry, ParseError> {
+    let (start, end, rest) = split_range_and_entry(input.to_string(), now);
+    if end.is_none() {
+        let first = rest.split(' ').next().unwrap_or_default();
+        // An offset or duration before the entry is only left in it when it's out of range
+        let out_of_range = rest.contains(' ') && is_duration(first);
+        if looks_like_time(first) || out_of_range {
+            return Err(ParseError::InvalidTime(first.to_string()));
+        }
+    }
+
+    // Notes can follow a leading `--` too, when there's nothing else
+    let rest = format!(" {}", rest);
+    let (body, notes) = match find_unescaped(&rest, " -- ") {
+        Some(i) => (&rest[..i], rest[i + 4..].trim()),
+        None => (rest.as_str(), ""),
+    };
+
+    let chars = unescape(body);
+    let mut segments = split_tags(&chars).into_iter();
+    let first = segments.next().unwrap_or_default();
+    let (project, activity) = match first.iter().position(|&(c, escaped)| c == ':' && !escaped) {
+        Some(i) => (text(&first[..i]), text(&first[i + 1..])),
+        None => (String::new(), text(&first)),
+    };
+    let tags = segments
+        .map(|segment| parse_tag(&text(&segment)))
+        .collect::<Result<Vec<Tag>, ParseError>>()?;
+
+    if project.is_empty() && activity.is_empty() && tags.is_empty() {
+        return Err(ParseError::Empty);
+    }
+
+    Ok(ParsedEntry {
+        entry: EntryRaw {
+            end: end.unwrap_or(now),
+            project,
+            activity,
+            tags,
+            notes: String::from(notes),
+        },
+        start,
+    })
+}
+
+/// Format an entry as input, with its full date and time, so [`parse_entry`] reads it back
+pub fn format_entry(entry: &EntryRaw) -> String {
+    let time_format = match entry.end.second() == 0 && entry.end.nanosecond() == 0 {
+        true => "%Y-%m-%d %H:%M",
+        false => "%Y-%m-%d %H:%M:%S%.f",
+    };
+    let mut body = if !entry.project.is_empty() {
+        format!(
+            "{}: {}",
+            escape(&entry.project, true),
+            escape(&entry.activity, false)
+        )
+    } else if entry.activity.contains(':') {
+        // An empty project keeps the activity's colons from being read as one
+        format!(": {}", escape(&entry.activity, false))
+    } else {
+        escape(&entry.activity, false)
+    };
+
+    // A leading duration would be read as the entry's length
+    let first_word = body.split(' ').next().unwrap_or_default();
+    if first_word.ends_with(['h', 'm', 's']) && parse_duration(first_word).is_some() {
+        body.insert(0, '\\');
+    }
+
+    let mut parts = vec![entry.end.format(time_format).to_string()];
+    if !body.trim().is_empty() {
+        parts.push(body.trim_end().to_string());
+    }
+    parts.extend(
+        entry
+            .tags
+            .iter()
+            .map(|t| format!("+{}", escape(&t.to_string(), false))),
+    );
+    if !entry.notes.is_empty() {
+        parts.push(format!("-- {}", entry.notes));
+    }
+    parts.join(" ")
+}
+
+/// Parse a `+tag` or `+key=value` tag, without the `+`
+pub fn parse_tag(value: &str) -> Result<Tag, ParseError> {
+    let tag = split_tag(value);
+    if tag.key.is_empty() || value.contains(',') {
+        return Err(ParseError::InvalidTag(value.to_string()));
+    }
+    Ok(tag)
+}
+
+/// Split `key=value`. An empty value, as in `key=`, is no value
+fn split_tag(value: &str) -> Tag {
+    match value.split_once('=') {
+        Some((key, value)) => Tag {
+            key: String::from(key.trim()),
+            value: Some(String::from(value.trim())).filter(|v| !v.is_empty()),
+        },
+        None => Tag {
+            key: String::from(value.trim()),
+            value: None,
+        },
+    }
+}
+
+impl FromStr for Tag {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_tag(s)
+    }
+}
+
+impl Display for Tag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.value {
+            Some(value) => write!(f, "{}={}", self.key, value),
+            None => write!(f, "{}", self.key),
+        }
+    }
+}
+
+/// Parse the log's time column
+pub fn parse_datetime(value: &str) -> Result<NaiveDateTime, ParseError> {
+    NaiveDateTime::parse_from_str(value, LOG_DATETIME_FORMAT)
+        .map_err(|_| ParseError::InvalidDateTime(value.to_string()))
+}
+
+/// Format a time for the log's time column
+pub fn format_datetime(datetime: &NaiveDateTime) -> String {
+    datetime.format(LOG_DATETIME_FORMAT).to_string()
+}
+
+/// Parse the log's tag column. Tags are separated by commas
+pub fn parse_tags(value: &str) -> Vec<Tag> {
+    if value.is_empty() {
+        vec![]
+    } else {
+        value.split(',').map(split_tag).collect()
+    }
+}
+
+/// Format tags for the log's tag column
+pub fn format_tags(tags: &[Tag]) -> String {
+    let tags: Vec<String> = tags.iter().map(Tag::to_string).collect();
+    tags.join(", ")
+}
+
+/// Format an entry as a line of the log: duration, end time and title
+pub fn format_line(entry: &Entry) -> String {
+    let duration = entry.duration();
+    let duration_display = if duration.is_zero() {
+        String::new()
+    } else {
+        format_duration(duration)
+    };
+
+    format!(
+        "{:<8} {:<6} {}",
+        duration_display,
+        entry.end.format("%H:%M"),
+        entry.display_sans_time()
+    )
+}
+
+/// Format a duration as `1h 30m`
+pub fn format_duration(duration: Duration) -> String {
+    format!("{}h {}m", duration.num_hours(), duration.num_minutes() % 60)
+}
+
+/// Punctuation and digits can be escaped with `\`
+fn is_escapable(c: char) -> bool {
+    c.is_ascii_punctuation() || c.is_ascii_digit()
+}
+
+/// Escape `\`, a `+` starting a word, `--` and, if `colons`, `:` so they're read as text
+fn escape(value: &str, colons: bool) -> String {
+    let chars: Vec<char> = value.chars().collect();
+    let mut out = String::with_capacity(value.len());
+    for (i, &c) in chars.iter().enumerate() {
+        let word_start = i == 0 || chars[i - 1].is_whitespace();
+        let escaped = match c {
+            '\\' => true,
+            ':' => colons,
+            '+' => word_start,
+            '-' => chars.get(i + 1) == Some(&'-'),
+            _ => false,
+        };
+        if escaped {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Characters of `value`, each with whether it was escaped
+fn unescape(value: &str) -> Vec<(char, bool)> {
+    let mut chars = vec![];
+    let mut iter = value.chars().peekable();
+    while let Some(c) = iter.next() {
+        match iter.peek() {
+            Some(&next) if c == '\\' && is_escapable(next) => {
+                iter.next();
+                chars.push((next, true));
+            }
+            _ => chars.push((c, false)),
+        }
+    }
+    chars
+}
+
+/// Byte index of the first `pattern` in `value` that doesn't start on an escaped character
+fn find_unescaped(value: &str, pattern: &str) -> Option<usize> {
+    let mut iter = value.char_indices().peekable();
+    while let Some((i, c)) = iter.next() {
+        if c == '\\' && iter.peek().map_or(false, |&(_, next)| is_escapable(next)) {
+            iter.next();
+        } else if value[i..].starts_with(pattern) {
+            return Some(i);
+        }
+    }
+    None
+}
+
+/// Split at each unescaped `+` that starts a word and is followed by a tag
+fn split_tags(chars: &[(char, bool)]) -> Vec<Vec<(char, bool)>> {
+    let mut segments = vec![vec![]];
+    for (i, &(c, escaped)) in chars.iter().enumerate() {
+        let word_start = i == 0 || chars[i - 1].0.is_whitespace();
+        let followed = chars
+            .get(i + 1)
+            .map_or(false, |(next, _)| !next.is_whitespace());
+        if c == '+' && !escaped && word_start && followed {
+            segments.push(vec![]);
+        } else if let Some(segment) = segments.last_mut() {
+            segment.push((c, escaped));
+        }
+    }
+    segments
+}
+
+/// Unescaped, trimmed text
+fn text(chars: &[(char, bool)]) -> String {
+    chars
+        .iter()
+        .map(|(c, _)| c)
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+/// Whether the first word of an entry was probably meant as a time, like `25:00` or `@9:75`
+fn looks_like_time(word: &str) -> bool {
+    let word = word.strip_prefix('@').unwrap_or(word);
+    word.starts_with(|c: char| c.is_ascii_digit())
+        && word.contains(':')
+        && !word.ends_with(':')
+        && word
+            .chars()
+            .all(|c| c.is_ascii_digit() || ":-apm".contains(c))
+}
+
+/// Whether a word is an offset (`-15`) or a duration with units (`45m`)
+fn is_duration(word: &str) -> bool {
+    word.strip_prefix('-')
+        .or_else(|| word.ends_with(['h', 'm', 's']).then_some(word))
+        .and_then(parse_duration)
+        .is_some()
+}
+
+/// Split a leading `start-end` range, duration or time off an entry, resolving it relative to `datetime`.
+///
+/// Ranges are two clock times like `09:00-10:30` or `9am-10:30am`, optionally after
+/// a date (`yesterday 09:00-10:30`). A range ending before it starts ends the next day.
+/// Durations like `45m` or `1h30m` make a range ending at `datetime`, or at a time
+/// given before them (`17:00 45m`).
+/// Returns the start for ranges and durations, the end, and the rest of the entry.
+/// See [`split_time_and_entry`] for the single time forms.
+pub fn split_range_and_entry(
+    value: String,
+    datetime: NaiveDateTime,
+) -> (Option<NaiveDateTime>, Option<NaiveDateTime>, String) {
+    let date = datetime.date();
+
+    if let Some((first, rest)) = value.split_once(' ') {
+        if let Some((start, end)) = parse_range(first, date) {
+            return (Some(start), Some(end), rest.to_string());
+        } else if let Some((second, rest)) = rest.split_once(' ') {
+            if let Some((start, end)) =
+                parse_date(first, date).and_then(|date| parse_range(second, date))
+            {
+                return (Some(start), Some(end), rest.to_string());
+            }
+        }
+    }
+
+    let (end, rest) = split_time_and_entry(value, datetime);
+    if let Some((duration, entry)) = rest.split_once(' ') {
+        // Units are required so a plain number can start an activity
+        if duration.ends_with(['h', 'm', 's']) {
+            let end = end.unwrap_or(datetime);
+            if let Some(start) = parse_duration(duration).and_then(|d| end.checked_sub_signed(d)) {
+                return (Some(start), Some(end), entry.to_string());
+            }
+        }
+    }
+    (None, end, rest)
+}
+
+/// Split a leading time off an entry, resolving it relative to `datetime`.
+///
+/// The time is one of
+/// - a clock time: `17:00`, `@17:00`, `9am` or `5:30pm`
+/// - a date then a clock time: `yesterday 17:00`, `friday 9am` or `2023-06-14 @10:15`
+/// - an offset back from `datetime`: `-15`, `-1:30`, `-1h30m` or `-90s`
+///
+/// and must be followed by a space, so a half typed time isn't taken as complete.
+pub fn split_time_and_entry(
+    value: String,
+    datetime: NaiveDateTime,
+) -> (Option<NaiveDateTime>, String) {
+    let date = datetime.date();
+
+    if let Some((time, rest)) = value.split_once(' ') {
+        if let Some(time) = parse_clock_time(time) {
+            return (Some(NaiveDateTime::new(date, time)), rest.to_string());
+        } else if let Some(offset) = time.strip_prefix('-').and_then(parse_duration) {
+            // Offsets too long for a date aren't times
+            if let Some(time) = datetime.checked_sub_signed(offset) {
+                return (Some(time), rest.to_string());
+            }
+        } else if let Some((clock, rest)) = rest.split_once(' ') {
+            if let (Some(date), Some(clock)) = (parse_date(time, date), parse_clock_time(clock)) {
+                return (Some(NaiveDateTime::new(date, clock)), rest.to_string());
+            }
+        }
+    }
+
+    (None, value)
+}
+
+/// Parse a date like `2023-06-14`, `yesterday` or `last friday`, relative to `today`
+pub fn parse_date(value: &str, today: NaiveDate) -> Option<NaiveDate> {
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Some(date);
+    }
+    // fuzzydate takes a lone number as a time of day
+    if value.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    fuzzydate::parse_relative_to(value, today.and_hms_opt(0, 0, 0)?)
+        .ok()
+        .map(|datetime| datetime.date())
+}
+
+/// Two clock times joined by `-` on `date`
+fn parse_range(value: &str, date: NaiveDate) -> Option<(NaiveDateTime, NaiveDateTime)> {
+    let (start, end) = value.split_once('-')?;
+    let start = NaiveDateTime::new(date, parse_clock_time(start)?);
+    let mut end = NaiveDateTime::new(date, parse_clock_time(end)?);
+    if end <= start {
+        end = end.checked_add_signed(Duration::days(1))?;
+    }
+    Some((start, end))
+}
+
+/// `17:00`, `17:00:30`, `@17:00`, `9am` or `5:30pm`
+fn parse_clock_time(value: &str) -> Option<NaiveTime> {
+    let value = value.strip_prefix('@').unwrap_or(value);
+    if let Ok(time) = NaiveTime::parse_from_str(value, "%H:%M") {
+        return Some(time);
+    }
+    if let Ok(time) = NaiveTime::parse_from_str(value, "%H:%M:%S%.f") {
+        return Some(time);
+    }
+
+    let value = value.to_lowercase();
+    let (clock, pm) = match value.strip_suffix("am") {
+        Some(clock) => (clock, false),
+        None => (value.strip_suffix("pm")?, true),
+    };
+    let (hour, minute) = clock.split_once(':').unwrap_or((clock, "00"));
+    let hour: u32 = hour.parse().ok()?;
+    let minute: u32 = minute.parse().ok()?;
+    if !(1..=12).contains(&hour) {
+        return None;
+    }
+    NaiveTime::from_hms_opt(hour % 12 + if pm { 12 } else { 0 }, minute, 0)
+}
+
+/// Parse a duration as minutes (`15`), hours and minutes (`1:30`), or
+/// units (`1h30m`, `45m`, `90s`)
+pub fn parse_duration(value: &str) -> Option<Duration> {
+    if let Ok(minutes) = value.parse::<u32>() {
+        return Duration::try_minutes(minutes as i64);
+    }
+
+    if let Some((hours, minutes)) = value.split_once(':') {
+        if minutes.len() != 2 {
+            return None;
+        }
+        let hours: u32 = hours.parse().ok()?;
+        let minutes: u32 = minutes.parse().ok()?;
+        if minutes >= 60 {
+            return None;
+        }
+        return Duration::try_hours(hours as i64)?
+            .checked_add(&Duration::try_minutes(minutes as i64)?);
+    }
+
+    if value.is_empty() {
+        return None;
+    }
+    let mut total = Duration::zero();
+    let mut digits = String::new();
+    for c in value.chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+        let n: u32 = digits.parse().ok()?;
+        digits.clear();
+        let part = match c {
+            'h' => Duration::try_hours(n as i64),
+            'm' => Duration::try_minutes(n as i64),
+            's' => Duration::try_seconds(n as i64),
+            _ => return None,
+        };
+        total = total.checked_add(&part?)?;
+    }
+    digits.is_empty().then_some(total)
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{Duration, NaiveDate, NaiveDateTime};
+
+    use super::{
+        format_entry, parse_date, parse_duration, parse_entry, split_range_and_entry,
+        split_time_and_entry, ParseError, ParsedEntry,
+    };
+    use crate::entries::{EntryRaw, Tag};
+
+    #[test]
+    fn test_split_time_forms() {
+        let datetime = NaiveDate::from_ymd_opt(2023, 6, 14)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap();
+        let at = |d: u32, h: u32, m: u32, s: u32| {
+            NaiveDate::from_ymd_opt(2023, 6, d)
+                .unwrap()
+                .and_hms_opt(h, m, s)
+                .unwrap()
+        };
+        let split = |value: &str| split_time_and_entry(String::from(value), datetime);
+
+        assert_eq!(
+            split("9am PROJ: x"),
+            (Some(at(14, 9, 0, 0)), "PROJ: x".into())
+        );
+        assert_eq!(split("5:30pm x"), (Some(at(14, 17, 30, 0)), "x".into()));
+        assert_eq!(split("12am x").0, Some(at(14, 0, 0, 0)));
+        assert_eq!(split("@10:15 x").0, Some(at(14, 10, 15, 0)));
+        assert_eq!(
+            split("yesterday 17:00 x"),
+            (Some(at(13, 17, 0, 0)), "x".into())
+        );
+        assert_eq!(split("2023-06-12 9am x").0, Some(at(12, 9, 0, 0)));
+        assert_eq!(split("-1h30m x").0, Some(at(14, 10, 30, 0)));
+        assert_eq!(split("-90s x").0, Some(at(14, 11, 58, 30)));
+        // Needs a space after the time before it counts
+        assert_eq!(split("yesterday 17:00"), (None, "yesterday 17:00".into()));
+        assert_eq!(split("today meeting x").0, None);
+        assert_eq!(split("13pm x").0, None);
+    }
+
+    #[test]
+    fn test_split_range() {
+        let datetime = NaiveDate::from_ymd_opt(2023, 6, 14)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap();
+        let at = |d: u32, h: u32, m: u32| {
+            NaiveDate::from_ymd_opt(2023, 6, d)
+                .unwrap()
+                .and_hms_opt(h, m, 0)
+                .unwrap()
+        };
+        let split = |value: &str| split_range_and_entry(String::from(value), datetime);
+
+        assert_eq!(
+            split("09:00-10:30 PROJ: x"),
+            (Some(at(14, 9, 0)), Some(at(14, 10, 30)), "PROJ: x".into())
+        );
+        assert_eq!(
+            split("9am-1pm x"),
+            (Some(at(14, 9, 0)), Some(at(14, 13, 0)), "x".into())
+        );
+        assert_eq!(
+            split("yesterday 23:00-01:00 x"),
+            (Some(at(13, 23, 0)), Some(at(14, 1, 0)), "x".into())
+        );
+        assert_eq!(
+            split("45m PROJ: x"),
+            (Some(at(14, 11, 15)), Some(at(14, 12, 0)), "PROJ: x".into())
+        );
+        assert_eq!(
+            split("17:00 1h30m x"),
+            (Some(at(14, 15, 30)), Some(at(14, 17, 0)), "x".into())
+        );
+        assert_eq!(
+            split("yesterday 9am 2h x"),
+            (Some(at(13, 7, 0)), Some(at(13, 9, 0)), "x".into())
+        );
+        assert_eq!(split("10:30 x"), (None, Some(at(14, 10, 30)), "x".into()));
+        assert_eq!(
+            split("10:30 15 x"),
+            (None, Some(at(14, 10, 30)), "15 x".into())
+        );
+        assert_eq!(split("-15 x"), (None, Some(at(14, 11, 45)), "x".into()));
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("15"), Some(Duration::minutes(15)));
+        assert_eq!(parse_duration("90"), Some(Duration::minutes(90)));
+        assert_eq!(parse_duration("1:30"), Some(Duration::minutes(90)));
+        assert_eq!(parse_duration("0:05"), Some(Duration::minutes(5)));
+        assert_eq!(parse_duration("12:00"), Some(Duration::hours(12)));
+        assert_eq!(parse_duration("1h30m"), Some(Duration::minutes(90)));
+        assert_eq!(parse_duration("2h"), Some(Duration::hours(2)));
+        assert_eq!(parse_duration("45m"), Some(Duration::minutes(45)));
+        assert_eq!(parse_duration("90s"), Some(Duration::seconds(90)));
+        assert_eq!(parse_duration(""), None);
+        assert_eq!(parse_duration("1:3"), None);
+        assert_eq!(parse_duration("1:60"), None);
+        assert_eq!(parse_duration("1h30"), None);
+        assert_eq!(parse_duration("h"), None);
+        assert_eq!(parse_duration("1d"), None);
+    }
+
+    #[test]
+    fn test_parse_date() {
+        let date = |y: i32, m: u32, d: u32| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        let today = date(2023, 6, 14);
+
+        assert_eq!(parse_date("2026-03-02", today), Some(date(2026, 3, 2)));
+        assert_eq!(parse_date("yesterday", today), Some(date(2023, 6, 13)));
+        assert_eq!(parse_date("last friday", today), Some(date(2023, 6, 9)));
+        assert_eq!(parse_date("14", today), None);
+    }
+
+    #[test]
+    fn test_offsets() {
+        let at = |d: u32, h: u32, m: u32| {
+            NaiveDate::from_ymd_opt(2023, 6, d)
+                .unwrap()
+                .and_hms_opt(h, m, 0)
+                .unwrap()
+        };
+        let split = |value: &str, now| split_time_and_entry(String::from(value), now).0;
+
+        assert_eq!(split("-15 x", at(14, 12, 0)), Some(at(14, 11, 45)));
+        assert_eq!(split("-1:30 x", at(14, 12, 0)), Some(at(14, 10, 30)));
+        assert_eq!(split("-1h30m x", at(14, 12, 0)), Some(at(14, 10, 30)));
+        // Offsets past midnight land on the previous day
+        assert_eq!(split("-30 x", at(14, 0, 10)), Some(at(13, 23, 40)));
+        assert_eq!(split("-2:15 x", at(14, 1, 0)), Some(at(13, 22, 45)));
+
+        // Offsets and durations past the range of a date aren't times
+        assert_eq!(split("-4000000000h x", at(14, 12, 0)), None);
+        assert_eq!(
+            split_range_and_entry(String::from("4000000000h x"), at(14, 12, 0)),
+            (None, None, String::from("4000000000h x"))
+        );
+        assert_eq!(parse_duration(&"4000000000h".repeat(1000)), None);
+        assert_eq!(
+            parse_entry("-4000000000h x", at(14, 12, 0)),
+            Err(ParseError::InvalidTime(String::from("-4000000000h")))
+        );
+        assert_eq!(
+            parse_entry("4000000000h x", at(14, 12, 0)),
+            Err(ParseError::InvalidTime(String::from("4000000000h")))
+        );
+    }
+
+    fn now() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2023, 6, 14)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_parse_entry() {
+        let parsed = parse_entry("9am PROJ: work +bug +client=acme -- it was DNS", now()).unwrap();
+
+        assert_eq!(parsed.start, None);
+        assert_eq!(parsed.entry.end, now().date().and_hms_opt(9, 0, 0).unwrap());
+        assert_eq!(parsed.entry.project, "PROJ");
+        assert_eq!(parsed.entry.activity, "work");
+        assert_eq!(
+            parsed.entry.tags,
+            vec![
+                "bug".parse::<Tag>().unwrap(),
+                "client=acme".parse::<Tag>().unwrap()
+            ]
+        );
+        assert_eq!(parsed.entry.notes, "it was DNS");
+
+        let parsed = parse_entry("learn C++ +cpp", now()).unwrap();
+        assert_eq!(parsed.entry.activity, "learn C++");
+        assert_eq!(parsed.entry.tags.len(), 1);
+        assert_eq!(
+            parse_entry("45m x", now()).unwrap().start,
+            Some(now() - Duration::minutes(45))
+        );
+
+        let parsed = parse_entry("\\45m review", now()).unwrap();
+        assert_eq!(
+            (parsed.start, parsed.entry.activity.as_str()),
+            (None, "45m review")
+        );
+        assert_eq!(
+            parse_entry("ratio 1\\:2", now()).unwrap().entry.activity,
+            "ratio 1:2"
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(parse_entry("", now()), Err(ParseError::Empty));
+        assert_eq!(parse_entry("10:00 ", now()), Err(ParseError::Empty));
+        assert_eq!(
+            parse_entry("25:00 PROJ: x", now()),
+            Err(ParseError::InvalidTime(String::from("25:00")))
+        );
+        assert_eq!(
+            parse_entry("x +a,b", now()),
+            Err(ParseError::InvalidTag(String::from("a,b")))
+        );
+        assert_eq!(
+            parse_entry("x +=b", now()),
+            Err(ParseError::InvalidTag(String::from("=b")))
+        );
+        // Projects can still be numbers
+        assert!(parse_entry("2023: planning", now()).is_ok());
+    }
+
+    #[test]
+    fn test_format_round_trip() {
+        let end = NaiveDate::from_ymd_opt(2023, 6, 13)
+            .unwrap()
+            .and_hms_opt(23, 30, 0)
+            .unwrap();
+        let entry = |project: &str, activity: &str, tags: &[&str], notes: &str| EntryRaw {
+            end,
+            project: String::from(project),
+            activity: String::from(activity),
+            tags: tags.iter().map(|t| t.parse().unwrap()).collect(),
+            notes: String::from(notes),
+        };
+        let entries = vec![
+            entry("PROJ", "work", &["bug", "client=acme"], "it was DNS"),
+            entry("", "**arrive", &[], ""),
+            entry("", "ratio 1:2", &[], ""),
+            entry("a:b", "c: d", &[], ""),
+            entry("PROJ", "x +y -- z", &["a +b", "k=v=w"], "notes -- more"),
+            entry("", "45m review", &[], ""),
+            entry("C:\\path", "back\\slash \\+", &[], ""),
+            entry("", "", &["only"], ""),
+            entry("PROJ", "", &[], "just notes"),
+            EntryRaw {
+                end: end + Duration::milliseconds(1500),
+                ..entry("", "seconds", &[], "")
+            },
+        ];
+
+        for entry in entries {
+            let formatted = format_entry(&entry);
+            assert_eq!(
+                parse_entry(&formatted, now()),
+                Ok(ParsedEntry { entry, start: None }),
+                "{}",
+                formatted
+            );
+        }
+    }
+}
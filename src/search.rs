@@ -0,0 +1,169 @@
+use std::ops::Range;
+
+use chrono::{NaiveDateTime, Timelike};
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+/// Days for a title's recency weight to halve
+const RECENCY_HALF_LIFE_DAYS: f64 = 7.0;
+
+/// How often, how recently and at what times of day an entry title was logged
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TitleUse {
+    pub last_used: NaiveDateTime,
+    pub count: usize,
+    /// Uses by the hour they ended in
+    hours: [usize; 24],
+}
+
+impl TitleUse {
+    pub fn new(end: NaiveDateTime) -> TitleUse {
+        let mut title_use = TitleUse {
+            last_used: end,
+            count: 0,
+            hours: [0; 24],
+        };
+        title_use.record(end);
+        title_use
+    }
+
+    /// Count another use ending at `end`
+    pub fn record(&mut self, end: NaiveDateTime) {
+        self.count += 1;
+        self.last_used = self.last_used.max(end);
+        self.hours[end.hour() as usize] += 1;
+    }
+
+    /// Ranking for history suggestions at `now`. Higher for titles used often,
+    /// recently, and within an hour of `now`'s time of day
+    pub fn score(&self, now: NaiveDateTime) -> f64 {
+        let days = (now - self.last_used).num_minutes().max(0) as f64 / (24.0 * 60.0);
+        let recency = 1.0 / (1.0 + days / RECENCY_HALF_LIFE_DAYS);
+        let frequency = 1.0 + (self.count as f64).ln();
+
+        let hour = now.hour() as usize;
+        let near_now: usize = [(hour + 23) % 24, hour, (hour + 1) % 24]
+            .iter()
+            .map(|h| self.hours[*h])
+            .sum();
+        let time_of_day = 1.0 + near_now as f64 / self.count.max(1) as f64;
+
+        recency * frequency * time_of_day
+    }
+}
+
+/// Push `c` lowercased and without accents to `folded`.
+///
+/// Accents are the combining marks left after decomposing (NFD), so decomposed input
+/// folds the same. Letters that don't decompose but have a plain spelling get it from
+/// the table. Combining marks on their own fold to nothing
+fn fold_into(c: char, folded: &mut String) {
+    for d in std::iter::once(c).nfd() {
+        for l in d.to_lowercase() {
+            match l {
+                l if is_combining_mark(l) => {}
+                'ß' => folded.push_str("ss"),
+                'æ' => folded.push_str("ae"),
+                'œ' => folded.push_str("oe"),
+                'đ' | 'ð' => folded.push('d'),
+                'ħ' => folded.push('h'),
+                'ı' => folded.push('i'),
+                'ł' | 'ŀ' => folded.push('l'),
+                'ø' => folded.push('o'),
+                'ŧ' => folded.push('t'),
+                l => folded.push(l),
+            }
+        }
+    }
+}
+
+/// Text as it's indexed and searched, ignoring case and accents
+pub fn fold(text: &str) -> String {
+    let mut folded = String::with_capacity(text.len());
+    for c in text.chars() {
+        fold_into(c, &mut folded);
+    }
+    folded
+}
+
+/// Byte range of the first match of `needle` in `haystack`, ignoring case and accents
+pub fn find_folded(haystack: &str, needle: &str) -> Option<Range<usize>> {
+    let needle = fold(needle);
+
+    // Folding can change the length, so keep the range of the haystack each folded byte came from
+    let mut folded = String::with_capacity(haystack.len());
+    let mut origins: Vec<Range<usize>> = Vec::with_capacity(haystack.len());
+    for (i, c) in haystack.char_indices() {
+        let before = folded.len();
+        fold_into(c, &mut folded);
+        origins.extend(std::iter::repeat(i..i + c.len_utf8()).take(folded.len() - before));
+    }
+
+    let found = folded.find(&needle)?;
+    let start = origins.get(found).map_or(haystack.len(), |o| o.start);
+    if needle.is_empty() {
+        return Some(start..start);
+    }
+    // Up to the next character that folds to something, taking in any accents after the match
+    let last = &origins[found + needle.len() - 1];
+    let end = origins[found + needle.len()..]
+        .iter()
+        .find(|o| o.start >= last.end)
+        .map_or(haystack.len(), |o| o.start);
+    Some(start..end)
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{Duration, NaiveDate, NaiveDateTime};
+
+    use super::{find_folded, fold, TitleUse};
+
+    fn at(day: u32, hour: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2023, 6, day)
+            .unwrap()
+            .and_hms_opt(hour, 0, 0)
+            .unwrap()
+    }
+
+    fn uses(ends: &[NaiveDateTime]) -> TitleUse {
+        let mut title_use = TitleUse::new(ends[0]);
+        for end in &ends[1..] {
+            title_use.record(*end);
+        }
+        title_use
+    }
+
+    #[test]
+    fn test_score() {
+        let now = at(20, 9);
+        let yesterday = uses(&[at(19, 15)]);
+        let often = uses(&[at(1, 15), at(2, 15), at(5, 15), at(8, 15), at(12, 15)]);
+        let mornings = uses(&[at(12, 9)]);
+        let afternoons = uses(&[at(12, 15)]);
+
+        assert!(yesterday.score(now) > uses(&[at(10, 15)]).score(now));
+        assert!(often.score(now) > uses(&[at(12, 15)]).score(now));
+        assert!(mornings.score(now) > afternoons.score(now));
+        // Long unused titles still rank by frequency
+        let later = now + Duration::days(3650);
+        assert!(often.score(later) > yesterday.score(later));
+    }
+
+    #[test]
+    fn test_fold() {
+        assert_eq!(fold("Café Ĳsselmeer ÅNGSTRÖM"), "cafe ĳsselmeer angstrom");
+        assert_eq!(find_folded("PROJ: Résumé", "resu"), Some(6..11));
+        assert_eq!(find_folded("PROJ: Résumé", "xyz"), None);
+        assert_eq!(find_folded("abc", ""), Some(0..0));
+
+        // Decomposed accents, letters without a decomposition, and Vietnamese
+        assert_eq!(fold("Cafe\u{301} Straße Łódź"), "cafe strasse lodz");
+        assert_eq!(fold("Tiếng Việt Đà Nẵng"), "tieng viet da nang");
+        assert_eq!(
+            find_folded("PROJ: Re\u{301}sume\u{301}", "résumé"),
+            Some(6..16)
+        );
+        assert_eq!(find_folded("Straße", "strasse"), Some(0..7));
+        assert_eq!(find_folded("Straße", "ss"), Some(4..6));
+    }
+}
@@ -1,26 +1,53 @@
 use std::{error::Error, io::Write};
 
 use chrono::NaiveDate;
+use clap::ValueEnum;
 
 use crate::{
     app::App,
+    backup, check,
     entries::{self, EntryGroup},
+    export, parse,
 };
 
-pub fn log(app: &App, date: Option<Vec<String>>, csv_print: bool) -> Result<(), Box<dyn Error>> {
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum ExportFormat {
+    /// hledger/ledger timeclock
+    Ledger,
+    /// Emacs org-mode CLOCK lines
+    Org,
+}
+
+/// Print the entries for a date, optionally only those with a `tag` filter and
+/// with totals by the values of the `by` tag key
+pub fn log(
+    app: &App,
+    date: Option<Vec<String>>,
+    csv_print: bool,
+    tag: Option<String>,
+    by: Option<String>,
+) -> Result<(), Box<dyn Error>> {
+    if csv_print && by.is_some() {
+        return Err("--by can't be used with --csv".into());
+    }
+
     let date = date
         .unwrap_or(vec![chrono::Local::now().date_naive().to_string()])
         .join(" ");
 
     let date_time = fuzzydate::parse(&date)?;
     let date = date_time.date();
-    let entry_group =
-        entries::read_all_date(&app.log_contents(), date, app.config.virtual_midnight)?;
+    let mut entry_group =
+        entries::read_all_date(&app.log_contents()?, date, app.config.virtual_midnight)
+            .map_err(|e| e.in_file(&app.log_path()))?;
+    if let Some(tag) = tag {
+        entry_group = entry_group.with_tag(&tag);
+    }
 
     if csv_print {
         log_csv(entry_group)
     } else {
-        log_pretty(date, entry_group)
+        log_pretty(app, date, entry_group, by)
     }
 }
 
@@ -28,11 +55,17 @@ fn log_csv(entry_group: EntryGroup) -> Result<(), Box<dyn Error>> {
     for entry in entry_group.entries.iter() {
         std::io::stdout().write_all(
             format!(
-                "\"{}\", \"{}\", \"{}\", \"{}\"\n",
+                "\"{}\", \"{}\", \"{}\", \"{}\", \"{}\"\n",
                 &entry.end.to_string(),
                 &entry.project,
                 &entry.activity,
-                &entry.tags.join(",")
+                &entry
+                    .tags
+                    .iter()
+                    .map(|t| t.to_string())
+                    .collect::<Vec<String>>()
+                    .join(","),
+                &entry.notes
             )
             .as_bytes(),
         )?;
@@ -40,21 +73,104 @@ fn log_csv(entry_group: EntryGroup) -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
-fn log_pretty(date: NaiveDate, entry_group: EntryGroup) -> Result<(), Box<dyn Error>> {
+fn log_pretty(
+    app: &App,
+    date: NaiveDate,
+    entry_group: EntryGroup,
+    by: Option<String>,
+) -> Result<(), Box<dyn Error>> {
     std::io::stdout().write_all(format!("--{}--\n", date).as_bytes())?;
     for entry in entry_group.entries.iter() {
         std::io::stdout().write_all(format!("{}\n", entry).as_bytes())?;
+        if !entry.notes.is_empty() {
+            std::io::stdout().write_all(format!("{:<16}{}\n", "", entry.notes).as_bytes())?;
+        }
+    }
+    for (category, total) in entry_group.totals(&app.config.categories) {
+        std::io::stdout().write_all(
+            format!("{}: {}\n", category.name, parse::format_duration(total)).as_bytes(),
+        )?;
+    }
+    if let Some(key) = by {
+        std::io::stdout().write_all(format!("--By {}--\n", key).as_bytes())?;
+        for (value, total) in entry_group.totals_by_tag(&key, &app.config.categories) {
+            std::io::stdout().write_all(
+                format!(
+                    "{}: {}\n",
+                    value.as_deref().unwrap_or("(none)"),
+                    parse::format_duration(total)
+                )
+                .as_bytes(),
+            )?;
+        }
     }
-    std::io::stdout()
-        .write_all(format!("On task: {}\n", entry_group.time_on_task_display()).as_bytes())?;
-    std::io::stdout()
-        .write_all(format!("Other: {}\n", entry_group.time_off_task_display()).as_bytes())?;
     Ok(())
 }
 
-pub fn add(app: &App, entry: Option<Vec<String>>) {
+pub fn add(app: &mut App, entry: Option<Vec<String>>) -> Result<(), Box<dyn Error>> {
     if let Some(entry) = entry {
-        app.add_log(entry.join(" "))
-            .expect("Error adding log entry")
+        app.add_log(entry.join(" "))?;
+    }
+    Ok(())
+}
+
+pub fn export(app: &App, format: ExportFormat) -> Result<(), Box<dyn Error>> {
+    let days = entries::read_all_by_date(&app.log_contents()?, app.config.virtual_midnight)
+        .map_err(|e| e.in_file(&app.log_path()))?;
+
+    let out = match format {
+        ExportFormat::Ledger => export::ledger(&days, &app.config.ledger, &app.config.categories),
+        ExportFormat::Org => export::org(&days),
+    };
+
+    std::io::stdout().write_all(out.as_bytes())?;
+    Ok(())
+}
+
+/// List backups of the log, or diff/restore the backup numbered `number` in that list
+pub fn restore(app: &App, number: Option<usize>, diff: bool) -> Result<(), Box<dyn Error>> {
+    let log_path = app.log_path();
+    let dir = backup::dir(&log_path);
+    let backups = backup::list(&dir, &log_path)?;
+
+    let Some(number) = number else {
+        if backups.is_empty() {
+            std::io::stdout().write_all(b"No backups\n")?;
+        }
+        for (i, path) in backups.iter().enumerate() {
+            std::io::stdout()
+                .write_all(format!("{:>3}  {}\n", i + 1, path.display()).as_bytes())?;
+        }
+        return Ok(());
+    };
+
+    let backup = backups
+        .get(number.wrapping_sub(1))
+        .ok_or(format!("No backup numbered {}", number))?;
+
+    if diff {
+        std::io::stdout().write_all(backup::diff(backup, &log_path)?.as_bytes())?;
+    } else {
+        backup::restore(&dir, backup, &log_path, app.config.backups)?;
+        std::io::stdout().write_all(format!("Restored {}\n", backup.display()).as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Print problems found in the log. Returns the number of problems
+pub fn check(app: &App, max_hours: i64) -> Result<usize, Box<dyn Error>> {
+    let max_duration = chrono::Duration::try_hours(max_hours)
+        .ok_or_else(|| format!("--max-hours {} is too large", max_hours))?;
+    let log_path = app.log_path();
+    let problems = check::check(
+        &app.log_contents()?,
+        app.config.virtual_midnight,
+        max_duration,
+        &app.config.categories,
+    );
+
+    for problem in &problems {
+        std::io::stdout().write_all(format!("{}:{}\n", log_path.display(), problem).as_bytes())?;
     }
+    Ok(problems.len())
 }
@@ -0,0 +1,51 @@
+use std::{
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::app::App;
+
+/// A log file in a temp directory of its own, removed with everything in it when dropped.
+///
+/// The directory is unique to the test and process so concurrent runs don't collide
+pub struct TempLog {
+    pub dir: PathBuf,
+    pub path: PathBuf,
+}
+
+impl TempLog {
+    pub fn new(contents: &str) -> TempLog {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "lipu-tenpo-test-{}-{}",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        // Left over from a killed run that had the same process id
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let path = dir.join("timelog.csv");
+        std::fs::write(&path, contents).unwrap();
+        TempLog { dir, path }
+    }
+
+    /// App using this log and the test config
+    pub fn app(&self) -> App {
+        App::new(
+            self.path.to_string_lossy().to_string(),
+            String::from("./test/config.toml"),
+        )
+        .unwrap()
+    }
+
+    pub fn contents(&self) -> String {
+        std::fs::read_to_string(&self.path).unwrap()
+    }
+}
+
+impl Drop for TempLog {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
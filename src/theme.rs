@@ -0,0 +1,178 @@
+use std::{collections::BTreeMap, str::FromStr};
+
+use ratatui::style::{Color, Modifier, Style};
+use serde::{Deserialize, Serialize};
+
+use crate::{config::Categories, entries::Entry};
+
+/// Styles used by the TUI, set in the `[theme]` config table
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct Theme {
+    /// Date bar when viewing today
+    pub today: ThemeStyle,
+    /// Date bar when viewing any other day
+    pub date: ThemeStyle,
+    pub border: ThemeStyle,
+    /// Hints shown around the input, like the resolved time
+    pub ghost: ThemeStyle,
+    pub summary: ThemeStyle,
+    /// Entries in off task categories
+    pub off_task: ThemeStyle,
+    /// Styles for the entries of each project
+    pub projects: BTreeMap<String, ThemeStyle>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            today: ThemeStyle(Style::default().bg(Color::Magenta).fg(Color::Black)),
+            date: ThemeStyle(Style::default().bg(Color::Gray).fg(Color::Black)),
+            border: ThemeStyle::default(),
+            ghost: ThemeStyle(Style::default().add_modifier(Modifier::DIM)),
+            summary: ThemeStyle::default(),
+            off_task: ThemeStyle(Style::default().add_modifier(Modifier::DIM)),
+            projects: BTreeMap::new(),
+        }
+    }
+}
+
+impl Theme {
+    /// Style of an entry's line in the log
+    pub fn entry(&self, entry: &Entry, categories: &Categories) -> Style {
+        let mut style = self
+            .projects
+            .get(entry.project.trim())
+            .map_or(Style::default(), |s| s.0);
+        if !categories.is_on_task(entry) {
+            style = style.patch(self.off_task.0);
+        }
+        style
+    }
+}
+
+/// A style written as `{ fg = "black", bg = "#ff00ff", modifiers = ["bold"] }`.
+///
+/// Colors are names (`red`, `lightblue`...), `#rrggbb` or 256 color indexes
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(try_from = "StyleConfig", into = "StyleConfig")]
+pub struct ThemeStyle(pub Style);
+
+#[derive(Deserialize, Serialize, Default)]
+#[serde(deny_unknown_fields)]
+struct StyleConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fg: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bg: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    modifiers: Vec<String>,
+}
+
+const MODIFIERS: [(&str, Modifier); 6] = [
+    ("bold", Modifier::BOLD),
+    ("dim", Modifier::DIM),
+    ("italic", Modifier::ITALIC),
+    ("underlined", Modifier::UNDERLINED),
+    ("reversed", Modifier::REVERSED),
+    ("crossed_out", Modifier::CROSSED_OUT),
+];
+
+fn parse_color(color: &str) -> Result<Color, String> {
+    Color::from_str(color).map_err(|_| format!("Unknown color \"{}\"", color))
+}
+
+impl TryFrom<StyleConfig> for ThemeStyle {
+    type Error = String;
+
+    fn try_from(config: StyleConfig) -> Result<Self, Self::Error> {
+        let mut style = Style::default();
+        if let Some(fg) = config.fg {
+            style = style.fg(parse_color(&fg)?);
+        }
+        if let Some(bg) = config.bg {
+            style = style.bg(parse_color(&bg)?);
+        }
+        for name in config.modifiers {
+            let (_, modifier) = MODIFIERS
+                .iter()
+                .find(|(n, _)| n.eq_ignore_ascii_case(&name))
+                .ok_or(format!("Unknown modifier \"{}\"", name))?;
+            style = style.add_modifier(*modifier);
+        }
+        Ok(ThemeStyle(style))
+    }
+}
+
+impl From<ThemeStyle> for StyleConfig {
+    fn from(style: ThemeStyle) -> Self {
+        StyleConfig {
+            fg: style.0.fg.map(|c| c.to_string()),
+            bg: style.0.bg.map(|c| c.to_string()),
+            modifiers: MODIFIERS
+                .iter()
+                .filter(|(_, m)| style.0.add_modifier.contains(*m))
+                .map(|(n, _)| n.to_string())
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::NaiveDateTime;
+    use ratatui::style::{Color, Modifier, Style};
+
+    use crate::{
+        config::Config,
+        entries::{Entry, EntryRaw},
+    };
+
+    #[test]
+    fn test_theme() {
+        let config: Config = toml::from_str(
+            r##"
+            virtual_midnight = 2
+
+            [theme]
+            today = { fg = "white", bg = "#005f87", modifiers = ["bold"] }
+
+            [theme.projects]
+            PROJ = { fg = "cyan" }
+            "##,
+        )
+        .unwrap();
+        let theme = &config.theme;
+        let entry = |input: &str| {
+            let raw = EntryRaw::from_string(String::from(input), NaiveDateTime::default()).unwrap();
+            theme.entry(&Entry::from_raw(&raw), &config.categories)
+        };
+
+        assert_eq!(
+            theme.today.0,
+            Style::default()
+                .fg(Color::White)
+                .bg(Color::Rgb(0, 0x5f, 0x87))
+                .add_modifier(Modifier::BOLD)
+        );
+        // Unset styles keep their defaults
+        assert_eq!(theme.date.0.bg, Some(Color::Gray));
+        assert_eq!(entry("PROJ: work"), Style::default().fg(Color::Cyan));
+        assert_eq!(
+            entry("PROJ: **lunch"),
+            Style::default().fg(Color::Cyan).add_modifier(Modifier::DIM)
+        );
+        assert_eq!(entry("OTHER: work"), Style::default());
+    }
+
+    #[test]
+    fn test_bad_theme() {
+        for theme in [
+            r#"border = { fg = "chartreuse-ish" }"#,
+            r#"summary = { modifiers = ["sparkly"] }"#,
+        ] {
+            let config = format!("virtual_midnight = 2\n[theme]\n{}", theme);
+            assert!(toml::from_str::<Config>(&config).is_err());
+        }
+    }
+}
@@ -1,25 +1,46 @@
-use chrono::Datelike;
+use chrono::{Datelike, NaiveDate};
 use ratatui::{prelude::*, widgets::*};
 
-use crate::app::{App, InputMode};
+use crate::{
+    app::{App, Completion, CompletionKind, InputMode, View},
+    entries,
+    keys::Action,
+    parse, search,
+};
+
+/// Where parts of the UI were last drawn, for mouse handling
+#[derive(Default, Debug, Clone)]
+pub struct Areas {
+    pub date: Rect,
+    /// Lines of the log, inside its borders and padding
+    pub log: Rect,
+    /// The input line while it shows a history suggestion
+    pub suggestion: Rect,
+    /// Each day of the date picker, when it's open
+    pub picker: Vec<(NaiveDate, Rect)>,
+}
 
 /// Renders the user interface widgets.
 pub fn render(app: &mut App, frame: &mut Frame<'_>) {
-    let entry_group = &app.current_entries;
-
     let root_layout = Layout::vertical([Constraint::Fill(1)]).margin(1);
 
     let [main_area] = root_layout.areas(frame.area());
+    let notes = app
+        .selected_entry()
+        .map(|e| e.notes.clone())
+        .unwrap_or_default();
+    let notes_height = if notes.is_empty() { 0 } else { 1 };
     let main_layout = Layout::vertical([
         Constraint::Max(1),
         Constraint::Min(2),
+        Constraint::Max(notes_height),
         Constraint::Max(2),
         Constraint::Max(3),
         Constraint::Max(1),
     ])
     .horizontal_margin(1);
 
-    let [date_area, log_area, summary_area, input_area, hotkeys_area] =
+    let [date_area, log_area, notes_area, summary_area, input_area, hotkeys_area] =
         main_layout.areas(main_area);
 
     // Date
@@ -27,9 +48,9 @@ pub fn render(app: &mut App, frame: &mut Frame<'_>) {
     let is_today = current_date == chrono::Local::now().naive_local().date();
     let is_today_str = if is_today { "@" } else { "" };
     let date_style = if is_today {
-        Style::default().bg(Color::Magenta).fg(Color::Black)
+        app.config.theme.today.0
     } else {
-        Style::default().bg(Color::Gray).fg(Color::Black)
+        app.config.theme.date.0
     };
 
     let title_block = Block::default()
@@ -42,13 +63,149 @@ pub fn render(app: &mut App, frame: &mut Frame<'_>) {
         ))
         .style(date_style);
     frame.render_widget(title_block, date_area);
+    app.areas.date = date_area;
+
+    match app.view {
+        View::Day => render_day(app, frame, [log_area, notes_area, summary_area], notes),
+        View::Week => {
+            app.areas.log = Rect::default();
+            render_week(app, frame, log_area.union(summary_area));
+        }
+    }
+
+    // Input
+    let theme = &app.config.theme;
+    let width = input_area.width.max(3) - 3;
+    let scroll = app.input.visual_scroll(width as usize);
+    let input_block = Block::default()
+        .padding(Padding::horizontal(1))
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(theme.border.0);
+
+    if let InputMode::GoingToDate = app.input_mode {
+        app.areas.suggestion = Rect::default();
+        render_date_prompt(app, frame, input_block, input_area);
+    } else {
+        let log_time = Span::styled(app.log_time.as_str(), theme.ghost.0);
+        let log_opening = Span::styled(app.log_opening.as_str(), theme.ghost.0);
+        let log_input = Span::raw(app.log_input.as_str());
+        let log_closing = Span::styled(app.log_closing.as_str(), theme.ghost.0);
+        // A suggestion that replaces the input is shown after it
+        let replaces = match app.suggestion_replaces_input {
+            true => " → ",
+            false => "",
+        };
+        let log_combined = Line::default().spans([
+            log_time.clone(),
+            log_opening.clone(),
+            log_input,
+            Span::styled(replaces, theme.ghost.0),
+            log_closing,
+        ]);
+        app.areas.suggestion = match app.log_opening.is_empty() && app.log_closing.is_empty() {
+            true => Rect::default(),
+            false => input_block.inner(input_area),
+        };
+        let input = Paragraph::new(log_combined)
+            .scroll((0, scroll as u16))
+            .block(input_block);
+        frame.render_widget(input, input_area);
+
+        match app.input_mode {
+            InputMode::Editing | InputMode::PickingDate | InputMode::GoingToDate => {}
+            InputMode::Logging | InputMode::SearchingHistory => {
+                frame.set_cursor_position(Position::new(
+                    input_area.x
+                        + 2
+                        + log_time.width() as u16
+                        + log_opening.width() as u16
+                        + ((app.input.visual_cursor()).max(scroll) - scroll) as u16,
+                    // input_area.x + 2 + app.current_log.len() as u16,
+                    input_area.y + 1,
+                ))
+            }
+        }
+    }
+
+    // Hotkeys, or the status line if there's an error to show
+    if let Some(status) = &app.status {
+        let status_block = Block::default().padding(Padding::horizontal(1)).red();
+        frame.render_widget(
+            Paragraph::new(status.as_str()).block(status_block),
+            hotkeys_area,
+        );
+    } else {
+        let hotkeys_block = Block::default().padding(Padding::horizontal(1)).dark_gray();
+        let keys = &app.config.keys;
+        let help = match app.input_mode {
+            InputMode::PickingDate => format!(
+                "Arrows: Move, {}/{}: Month, {}: Go to day, {}: Cancel",
+                keys.chords(&[Action::ScrollUp]),
+                keys.chords(&[Action::ScrollDown]),
+                keys.chords(&[Action::Commit]),
+                keys.chords(&[Action::Cancel]),
+            ),
+            InputMode::SearchingHistory => format!(
+                "Type to filter, {}/{}: Select, {}: Use entry, {}: Cancel",
+                keys.chords(&[Action::SearchBack]),
+                keys.chords(&[Action::SearchForward]),
+                keys.chords(&[Action::Commit, Action::AcceptHistory]),
+                keys.chords(&[Action::Cancel]),
+            ),
+            InputMode::GoingToDate => format!(
+                "Type a date like 2023-06-14, yesterday or last friday. {}: Go to day, {}: Cancel",
+                keys.chords(&[Action::Commit]),
+                keys.chords(&[Action::Cancel]),
+            ),
+            _ => keys.help(&app.view),
+        };
+        let hotkeys_help = match &app.completion {
+            Some(completion) => Paragraph::new(completion_line(completion)),
+            None => Paragraph::new(help),
+        }
+        .block(hotkeys_block);
+        frame.render_widget(hotkeys_help, hotkeys_area);
+    }
+
+    if let InputMode::SearchingHistory = app.input_mode {
+        render_history_search(app, frame, log_area.union(summary_area));
+    }
+
+    // Date picker
+    app.areas.picker.clear();
+    if let InputMode::PickingDate = app.input_mode {
+        render_date_picker(app, frame, main_area);
+    }
+}
+
+/// The log, the selected entry's notes and the summary of the current date
+fn render_day(app: &mut App, frame: &mut Frame<'_>, areas: [Rect; 3], notes: String) {
+    let [log_area, notes_area, summary_area] = areas;
+
+    // Keep the selected entry in view (the block has one line of bottom padding)
+    if let Some(selected) = app.selected_entry {
+        let visible = log_area.height.saturating_sub(1).max(1) as usize;
+        if selected < app.log_scroll {
+            app.scroll_log(selected);
+        } else if selected >= app.log_scroll + visible {
+            app.scroll_log(selected + 1 - visible);
+        }
+    }
+
+    let entry_group = &app.current_entries;
+    let theme = &app.config.theme;
 
     // Log
+    let categories = &app.config.categories;
     let mut log_items: Vec<Line> = entry_group
         .entries
         .iter()
-        .map(ratatui::text::Line::from)
+        .map(|entry| Line::from(entry).patch_style(theme.entry(entry, categories)))
         .collect();
+    if let Some(line) = app.selected_entry.and_then(|i| log_items.get_mut(i)) {
+        line.style = line.style.add_modifier(Modifier::REVERSED);
+    }
 
     let show_scrollbar = log_items.len() >= log_area.height.into();
     let scrollbar_constraint = if show_scrollbar { 5 } else { 0 };
@@ -72,12 +229,18 @@ pub fn render(app: &mut App, frame: &mut Frame<'_>) {
                     Borders::RIGHT
                 },
         )
-        .border_type(BorderType::Rounded);
-    if let Some(mut time_since_last) = entry_group.time_since_last_display() {
-        time_since_last.insert_str(0, "> ");
-        log_items.push(Line::from(time_since_last));
+        .border_type(BorderType::Rounded)
+        .border_style(theme.border.0);
+    // Only today's log is still running
+    let now = chrono::Local::now().naive_local();
+    if app.current_date == entries::effective_date(now, app.config.virtual_midnight) {
+        if let Some(mut time_since_last) = entry_group.time_since_last_display(now) {
+            time_since_last.insert_str(0, "> ");
+            log_items.push(Line::from(time_since_last));
+        }
     }
 
+    app.areas.log = log_block.inner(log_body_area);
     // let para: Paragraph = Paragraph::new(log_items.join("\n"))
     let para: Paragraph = Paragraph::new(Text::from(log_items))
         .scroll((app.log_scroll as u16, 0))
@@ -89,72 +252,320 @@ pub fn render(app: &mut App, frame: &mut Frame<'_>) {
         frame.render_stateful_widget(log_scrollbar, log_scrollbar_area, &mut app.scroll_state);
     }
 
-    // Summary
-    let summary_layout = Layout::horizontal(Constraint::from_percentages([50, 50]));
-    let [work_summary_area, other_summary_area] = summary_layout.areas(summary_area);
+    // Notes
+    let notes_block = Block::default().padding(Padding::horizontal(2)).italic();
+    frame.render_widget(Paragraph::new(notes).block(notes_block), notes_area);
 
-    let work_summary_block = Block::default()
-        .borders(Borders::BOTTOM | Borders::LEFT)
-        .border_type(BorderType::Rounded)
-        .padding(Padding {
-            left: 1,
-            ..Default::default()
-        });
-    let work_summary = Paragraph::new(format!("On task: {}", entry_group.time_on_task_display()))
-        .block(work_summary_block);
-    frame.render_widget(work_summary, work_summary_area);
+    // Summary, one column per category
+    let totals = entry_group.totals(categories);
+    let columns = totals.len().max(1) as u32;
+    let summary_layout =
+        Layout::horizontal((0..columns).map(|_| Constraint::Ratio(1, columns))).split(summary_area);
 
-    let other_summary_block = Block::default()
-        .borders(Borders::BOTTOM | Borders::RIGHT)
-        .border_type(BorderType::Rounded)
-        .padding(Padding {
-            right: 1,
-            ..Default::default()
+    for (i, (category, total)) in totals.iter().enumerate() {
+        let first = i == 0;
+        let last = i + 1 == totals.len();
+        let mut borders = Borders::BOTTOM;
+        let mut padding = Padding::default();
+        if first {
+            borders |= Borders::LEFT;
+            padding.left = 1;
+        }
+        if last {
+            borders |= Borders::RIGHT;
+            padding.right = 1;
+        }
+        let alignment = match (first, last) {
+            (true, _) => Alignment::Left,
+            (false, true) => Alignment::Right,
+            (false, false) => Alignment::Center,
+        };
+
+        let summary_block = Block::default()
+            .borders(borders)
+            .border_type(BorderType::Rounded)
+            .border_style(theme.border.0)
+            .padding(padding);
+        let summary = Paragraph::new(format!(
+            "{}: {}",
+            category.name,
+            parse::format_duration(*total)
+        ))
+        .alignment(alignment)
+        .style(theme.summary.0)
+        .block(summary_block);
+        frame.render_widget(summary, summary_layout[i]);
+    }
+}
+
+/// A column for each day of the current week with its entries and totals
+fn render_week(app: &mut App, frame: &mut Frame<'_>, area: Rect) {
+    let theme = &app.config.theme;
+    let categories = &app.config.categories;
+    let today = chrono::Local::now().date_naive();
+    let columns = Layout::horizontal([Constraint::Ratio(1, 7); 7]).split(area);
+
+    for (i, (date, group)) in app.week_entries().into_iter().enumerate() {
+        let mut header_style = match date == today {
+            true => theme.today.0,
+            false => Style::default(),
+        };
+        if date == app.current_date {
+            header_style = header_style.add_modifier(Modifier::BOLD);
+        }
+        let header = Line::from(date.format(" %a %m─%d ").to_string()).style(header_style);
+        let mut lines: Vec<Line> = group
+            .entries
+            .iter()
+            .map(|entry| {
+                Line::from(format!(
+                    "{} {}",
+                    entry.end.format("%H:%M"),
+                    entry.display_sans_time().trim()
+                ))
+                .patch_style(theme.entry(entry, categories))
+            })
+            .collect();
+        if !group.is_empty() {
+            lines.push(Line::default());
+            for (category, total) in group.totals(categories) {
+                lines.push(
+                    Line::from(format!(
+                        "{}: {}",
+                        category.name,
+                        parse::format_duration(total)
+                    ))
+                    .style(theme.summary.0),
+                );
+            }
+        }
+
+        let mut borders = Borders::TOP | Borders::LEFT;
+        if i == 6 {
+            borders |= Borders::RIGHT;
+        }
+        let block = Block::default()
+            .title(header)
+            .borders(borders)
+            .border_type(BorderType::Rounded)
+            .border_style(theme.border.0);
+        frame.render_widget(Paragraph::new(lines).block(block), columns[i]);
+    }
+}
+
+/// The completion candidates, with the one in the input highlighted
+fn completion_line(completion: &Completion) -> Line<'_> {
+    let label = match completion.kind {
+        CompletionKind::Project => "Projects: ",
+        CompletionKind::Tag => "Tags: ",
+    };
+    let mut spans = vec![Span::raw(label)];
+    for (i, candidate) in completion.candidates.iter().enumerate() {
+        if i > 0 {
+            spans.push(Span::raw(", "));
+        }
+        spans.push(match i == completion.index {
+            true => Span::styled(candidate.as_str(), Style::default().white().bold()),
+            false => Span::raw(candidate.as_str()),
         });
-    let other_summary = Paragraph::new(format!("Other: {}", entry_group.time_off_task_display()))
-        .alignment(Alignment::Right)
-        .block(other_summary_block);
-    frame.render_widget(other_summary, other_summary_area);
+    }
+    Line::from(spans)
+}
 
-    // Input
-    let width = input_area.width.max(3) - 3;
-    let scroll = app.input.visual_scroll(width as usize);
-    let input_block = Block::default()
-        .padding(Padding::horizontal(1))
+/// Every history match for the input, with when and how often it was logged
+fn render_history_search(app: &App, frame: &mut Frame<'_>, area: Rect) {
+    let matches = app.history_matches(chrono::Local::now().naive_local());
+    let theme = &app.config.theme;
+    let highlight = Style::default().add_modifier(Modifier::BOLD | Modifier::UNDERLINED);
+    let rows: Vec<Row> = matches
+        .iter()
+        .map(|(title, title_use)| {
+            let title: &str = (*title).into();
+            let (last_used, count) = match title_use {
+                Some(u) => (
+                    u.last_used.format("%Y-%m-%d").to_string(),
+                    format!("{}×", u.count),
+                ),
+                None => (String::new(), String::new()),
+            };
+            Row::new([
+                Cell::from(highlight_matches(title, &app.log_input, highlight)),
+                Cell::from(last_used),
+                Cell::from(Text::from(count).alignment(Alignment::Right)),
+            ])
+        })
+        .collect();
+
+    let block = Block::default()
+        .title(format!(" History ({}) ", matches.len()))
         .borders(Borders::ALL)
-        .border_type(BorderType::Rounded);
-
-    let log_time = Span::raw(app.log_time.as_str());
-    let log_opening = Span::styled(app.log_opening.as_str(), Style::default().dim());
-    let log_input = Span::raw(app.log_input.as_str());
-    let log_closing = Span::styled(app.log_closing.as_str(), Style::default().dim());
-    let log_combined = Line::default().spans([
-        log_time.clone(),
-        log_opening.clone(),
-        log_input,
-        log_closing,
+        .border_type(BorderType::Rounded)
+        .border_style(theme.border.0)
+        .padding(Padding::horizontal(1));
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Fill(1),
+            Constraint::Length(10),
+            Constraint::Length(5),
+        ],
+    )
+    .block(block)
+    .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    let mut state = TableState::default().with_selected(Some(app.history_cursor));
+
+    frame.render_widget(Clear, area);
+    frame.render_stateful_widget(table, area, &mut state);
+}
+
+/// `text` with the parts matching any word of `query` styled, ignoring case and accents
+fn highlight_matches<'a>(text: &'a str, query: &str, style: Style) -> Line<'a> {
+    if text.is_empty() {
+        return Line::default();
+    }
+    let mut matched = vec![false; text.len()];
+    for word in query.split_whitespace() {
+        let mut from = 0;
+        while let Some(found) = search::find_folded(&text[from..], word) {
+            // A word of only accents folds to nothing and matches everywhere
+            if found.is_empty() {
+                break;
+            }
+            matched[from + found.start..from + found.end]
+                .iter_mut()
+                .for_each(|m| *m = true);
+            from += found.end;
+        }
+    }
+
+    let mut spans: Vec<Span> = vec![];
+    let mut run_start = 0;
+    for (i, _) in text.char_indices().skip(1).chain([(text.len(), ' ')]) {
+        if i == text.len() || matched[i] != matched[run_start] {
+            let part = &text[run_start..i];
+            spans.push(match matched[run_start] {
+                true => Span::styled(part, style),
+                false => Span::raw(part),
+            });
+            run_start = i;
+        }
+    }
+    Line::from(spans)
+}
+
+/// The go to date prompt with the date it resolves to, in place of the input
+fn render_date_prompt(app: &App, frame: &mut Frame<'_>, block: Block, area: Rect) {
+    let prompt = Span::styled("Go to: ", Style::default().bold());
+    let preview = match app.date_prompt_date() {
+        Some(date) => date.format("  %a %Y-%m-%d").to_string(),
+        None => String::new(),
+    };
+    let width = area.width.saturating_sub(3 + prompt.width() as u16);
+    let scroll = app.date_input.visual_scroll(width as usize);
+    let cursor = app.date_input.visual_cursor().max(scroll) - scroll;
+    let line = Line::default().spans([
+        prompt.clone(),
+        Span::raw(app.date_input.value()),
+        Span::styled(preview, app.config.theme.ghost.0),
     ]);
-    let input = Paragraph::new(log_combined)
-        .scroll((0, scroll as u16))
-        .block(input_block);
-    frame.render_widget(input, input_area);
-
-    match app.input_mode {
-        InputMode::Editing => {}
-        InputMode::Logging => frame.set_cursor_position(Position::new(
-            input_area.x
-                + 2
-                + log_time.width() as u16
-                + log_opening.width() as u16
-                + ((app.input.visual_cursor()).max(scroll) - scroll) as u16,
-            // input_area.x + 2 + app.current_log.len() as u16,
-            input_area.y + 1,
-        )),
-    }
-
-    // Hotkeys
-    let hotkeys_block = Block::default().padding(Padding::horizontal(1)).dark_gray();
-    let hotkeys_help =
-        Paragraph::new("Ctrl+Left/Right: Prev/Next day, Ctrl+Home: Today").block(hotkeys_block);
-    frame.render_widget(hotkeys_help, hotkeys_area);
+    frame.render_widget(
+        Paragraph::new(line).scroll((0, scroll as u16)).block(block),
+        area,
+    );
+    frame.set_cursor_position(Position::new(
+        area.x + 2 + prompt.width() as u16 + cursor as u16,
+        area.y + 1,
+    ));
+}
+
+/// Month calendar around `app.picker_date`, centered in `area`
+fn render_date_picker(app: &mut App, frame: &mut Frame<'_>, area: Rect) {
+    let picked = app.picker_date;
+    let first = picked.with_day(1).unwrap_or(picked);
+    let offset = first.weekday().num_days_from_monday();
+    let days = (1..=31)
+        .filter_map(|day| first.with_day(day))
+        .collect::<Vec<NaiveDate>>();
+    let weeks = (offset as usize + days.len() + 6) / 7;
+
+    // Seven 2 wide columns with gaps, inside a border & padding
+    let width = 7 * 3 - 1 + 4;
+    let height = weeks as u16 + 3;
+    let popup = Rect {
+        x: area.x + area.width.saturating_sub(width) / 2,
+        y: area.y + area.height.saturating_sub(height) / 2,
+        width: width.min(area.width),
+        height: height.min(area.height),
+    };
+    let block = Block::default()
+        .title(picked.format(" %B %Y ").to_string())
+        .title_alignment(Alignment::Center)
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(app.config.theme.border.0)
+        .padding(Padding::horizontal(1));
+    let inner = block.inner(popup);
+    frame.render_widget(Clear, popup);
+    frame.render_widget(block, popup);
+    frame.render_widget(
+        Paragraph::new("Mo Tu We Th Fr Sa Su").dim(),
+        Rect { height: 1, ..inner },
+    );
+
+    let today = chrono::Local::now().date_naive();
+    for day in days {
+        let cell = offset + day.day0();
+        let rect = Rect {
+            x: inner.x + (cell % 7) as u16 * 3,
+            y: inner.y + 1 + (cell / 7) as u16,
+            width: 2,
+            height: 1,
+        }
+        .intersection(inner);
+        let mut style = match day == today {
+            true => app.config.theme.today.0,
+            false => Style::default(),
+        };
+        if app.log_index.has_day(day) {
+            style = style.add_modifier(Modifier::BOLD);
+        }
+        if day == picked {
+            style = style.add_modifier(Modifier::REVERSED);
+        }
+        frame.render_widget(
+            Paragraph::new(format!("{:>2}", day.day())).style(style),
+            rect,
+        );
+        app.areas.picker.push((day, rect));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ratatui::style::{Style, Stylize};
+
+    use super::highlight_matches;
+
+    #[test]
+    fn test_highlight_matches() {
+        let bold = Style::default().bold();
+        let spans: Vec<(String, bool)> = highlight_matches("PROJ: Débug proj", "proj dé", bold)
+            .spans
+            .iter()
+            .map(|span| (span.content.to_string(), span.style == bold))
+            .collect();
+
+        assert_eq!(
+            spans,
+            vec![
+                (String::from("PROJ"), true),
+                (String::from(": "), false),
+                (String::from("Dé"), true),
+                (String::from("bug "), false),
+                (String::from("proj"), true),
+            ]
+        );
+        assert!(highlight_matches("", "x", bold).spans.is_empty());
+        assert_eq!(highlight_matches("abc", "\u{301}", bold).spans.len(), 1);
+    }
 }